@@ -0,0 +1,57 @@
+//! Compares indexed trigger evaluation with a scan over every trigger
+
+use near_price_tracking_bot::commands::{
+    engine,
+    store::TriggerStore,
+    triggers::{Target, Trigger},
+};
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+const TRIGGERS_PER_CHAT: u32 = 10;
+
+/// Chats with levels spread on both sides of 6$, none of them reached at 6$
+fn store(chats: u32) -> TriggerStore<u32> {
+    let mut store = TriggerStore::default();
+
+    for chat_id in 0..chats {
+        for n in 0..TRIGGERS_PER_CHAT {
+            let offset = Decimal::new(i64::from((chat_id * TRIGGERS_PER_CHAT + n) % 5_000 + 1), 3);
+
+            let target = if n % 2 == 0 {
+                Target::Lower(Decimal::from(6) - offset)
+            } else {
+                Target::Higher(Decimal::from(6) + offset)
+            };
+
+            store.insert(chat_id, Trigger::new(target));
+        }
+    }
+
+    store
+}
+
+fn evaluate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluate");
+    let (price, now, portfolios) = (Decimal::from(6), Utc::now(), HashMap::new());
+
+    for chats in [100, 1_000, 10_000] {
+        let store = store(chats);
+        let triggers = chats * TRIGGERS_PER_CHAT;
+
+        group.bench_with_input(BenchmarkId::new("scan", triggers), &store, |b, store| {
+            b.iter(|| engine::scan(store, &portfolios, price, now));
+        });
+        group.bench_with_input(BenchmarkId::new("index", triggers), &store, |b, store| {
+            b.iter(|| engine::evaluate(store, &portfolios, price, now));
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, evaluate);
+criterion_main!(benches);
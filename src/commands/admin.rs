@@ -0,0 +1,323 @@
+use crate::commands::{
+    limits::Limits,
+    notifier::{Delivery, Notifier},
+    storage::{self, Storage},
+    store::TriggerStore,
+    triggers, AdminCommand, HandlerResult,
+};
+use crate::config::Config;
+use crate::metrics::METRICS;
+
+use anyhow::Context;
+use log::{error, info};
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use teloxide::{prelude::*, types::InputFile, utils::command::BotCommands};
+
+pub fn is_admin(msg: Message, config: Config) -> bool {
+    msg.from()
+        .is_some_and(|user| config.admin_ids.contains(&user.id))
+}
+
+fn format_age(age: Option<Duration>) -> String {
+    age.map_or_else(
+        || String::from("ніколи"),
+        |age| format!("{} с тому", age.as_secs()),
+    )
+}
+
+fn format_latency(latency: Option<Duration>) -> String {
+    latency.map_or_else(
+        || String::from("н/д"),
+        |latency| format!("{} мс", latency.as_millis()),
+    )
+}
+
+pub async fn help(bot: Bot, msg: Message) -> HandlerResult {
+    info!("Receiving admin help command...");
+
+    bot.send_message(msg.chat.id, AdminCommand::descriptions().to_string())
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub async fn stats(
+    bot: Bot,
+    msg: Message,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    limits: Limits,
+) -> HandlerResult {
+    info!("Receiving stats command...");
+
+    let (chats, triggers_count) = {
+        let locked_triggers = triggers.lock().await;
+
+        (
+            locked_triggers.len(),
+            locked_triggers.values().map(Vec::len).sum::<usize>(),
+        )
+    };
+
+    let message = format!(
+        "Чатів з тригерами: {chats}\n\
+         Тригерів: {triggers_count} з {} (на чат {})\n\
+         Спрацювань за добу: {}\n\
+         Затримка ціни: {} (середня {})",
+        limits.config().max_triggers,
+        limits.config().max_triggers_per_chat,
+        METRICS.fires_last_day(),
+        format_latency(METRICS.price_fetch_latency.last()),
+        format_latency(METRICS.price_fetch_latency.mean()),
+    );
+
+    bot.send_message(msg.chat.id, message)
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub async fn broadcast(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    notifier: Notifier,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    storage: Storage,
+) -> HandlerResult {
+    info!("Receiving broadcast command...");
+
+    if text.trim().is_empty() {
+        bot.send_message(msg.chat.id, "Формат: /broadcast <текст>")
+            .await
+            .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    }
+
+    let chat_ids = {
+        let locked_triggers = triggers.lock().await;
+
+        locked_triggers
+            .keys()
+            .copied()
+            .filter(|x| !locked_triggers.is_disabled(x))
+            .collect::<Vec<_>>()
+    };
+    let mut delivered = 0;
+    let mut unavailable = HashMap::new();
+
+    for chat_id in &chat_ids {
+        match notifier.send(*chat_id, &text, None, false).await {
+            Ok(Delivery::Sent) => delivered += 1,
+            Ok(Delivery::ChatUnavailable) => {
+                unavailable.insert(*chat_id, Vec::new());
+            }
+            Err(err) => error!("Failed to broadcast to chat {chat_id}: {err}"),
+        }
+    }
+
+    if !unavailable.is_empty() {
+        triggers::disable_chats(&storage, &triggers, unavailable).await;
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "Повідомлення надіслано в {delivered} з {} чатів",
+            chat_ids.len()
+        ),
+    )
+    .await
+    .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub async fn dump_triggers(
+    bot: Bot,
+    msg: Message,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+) -> HandlerResult {
+    info!("Receiving dump triggers command...");
+
+    let content = storage::encode(triggers::BACKUP_VERSION, &*triggers.lock().await)
+        .context("Failed to serialize triggers")?;
+
+    bot.send_document(
+        msg.chat.id,
+        InputFile::memory(content).file_name("triggers.bak"),
+    )
+    .await
+    .context("Failed to send Telegram document")?;
+
+    Ok(())
+}
+
+pub async fn feed_status(bot: Bot, msg: Message) -> HandlerResult {
+    info!("Receiving feed status command...");
+
+    let message = format!(
+        "Binance NEARUSDT:\n\
+         Остання ціна: {}\n\
+         Запитів: {}, помилок: {}\n\
+         Затримка: {}",
+        format_age(METRICS.price_age()),
+        METRICS.price_fetches.load(Ordering::Relaxed),
+        METRICS.price_fetch_errors.load(Ordering::Relaxed),
+        format_latency(METRICS.price_fetch_latency.last()),
+    );
+
+    bot.send_message(msg.chat.id, message)
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub async fn set_quota(bot: Bot, msg: Message, args: String, limits: Limits) -> HandlerResult {
+    info!("Receiving set quota command...");
+
+    let mut args = args.split_whitespace();
+    let chat_id = args.next().and_then(|x| x.parse::<i64>().ok()).map(ChatId);
+    let quota = match args.next() {
+        Some("default") => Some(None),
+        Some(quota) => quota.parse::<usize>().ok().map(Some),
+        None => None,
+    };
+
+    let (Some(chat_id), Some(quota), None) = (chat_id, quota, args.next()) else {
+        bot.send_message(
+            msg.chat.id,
+            "Формат: /setquota <chat id> <кількість | default>",
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    };
+
+    if let Err(err) = limits.set_quota(chat_id, quota).await {
+        error!("Failed to backup trigger quotas, due to: {}", err);
+    }
+
+    info!("Trigger quota of chat {chat_id} set to {quota:?}");
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "Ліміт тригерів чату {chat_id}: {}",
+            limits.quota(chat_id).await
+        ),
+    )
+    .await
+    .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::triggers::{Target, Trigger};
+    use crate::testing::{Harness, CHAT_ID};
+
+    use rust_decimal_macros::dec;
+    use teloxide::types::{ChatId, UserId};
+
+    async fn harness(is_admin: bool) -> Harness {
+        let mut harness = Harness::new();
+
+        if is_admin {
+            harness.config.admin_ids = vec![UserId(CHAT_ID as u64)];
+        }
+
+        harness
+            .triggers
+            .lock()
+            .await
+            .insert(ChatId(7), Trigger::new(Target::Lower(dec!(6))));
+
+        harness
+    }
+
+    #[tokio::test]
+    async fn hides_admin_commands_from_users() {
+        let mut harness = harness(false).await;
+
+        for command in [
+            "/broadcast hello",
+            "/dumptriggers",
+            "/setquota 7 5",
+            "/stats",
+        ] {
+            assert!(!harness.try_send(command).await, "{command} was handled");
+            assert!(harness.take_requests().await.is_empty());
+        }
+
+        assert_eq!(
+            harness.limits.quota(ChatId(7)).await,
+            harness.config.limits.max_triggers_per_chat
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcasts_to_chats_with_triggers() {
+        let mut harness = harness(true).await;
+
+        harness.send("/broadcast hello").await;
+
+        let requests = harness.take_requests().await;
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body["chat_id"], 7);
+        assert_eq!(requests[0].text(), Some("hello"));
+        assert_eq!(
+            requests[1].text(),
+            Some("Повідомлення надіслано в 1 з 1 чатів")
+        );
+
+        // Unreachable chats aren't counted and get disabled
+        harness
+            .triggers
+            .lock()
+            .await
+            .insert(ChatId(8), Trigger::new(Target::Lower(dec!(6))));
+        harness
+            .fail_next(serde_json::json!({
+                "ok": false,
+                "error_code": 403,
+                "description": "Forbidden: bot was blocked by the user",
+            }))
+            .await;
+
+        harness.send("/broadcast again").await;
+
+        let requests = harness.take_requests().await;
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            requests[2].text(),
+            Some("Повідомлення надіслано в 1 з 2 чатів")
+        );
+
+        let triggers = harness.triggers.lock().await;
+        // The chats are visited in no particular order, the first one gets the 403
+        let failed = ChatId(requests[0].body["chat_id"].as_i64().unwrap());
+        let delivered = if failed == ChatId(7) {
+            ChatId(8)
+        } else {
+            ChatId(7)
+        };
+        assert!(triggers.is_disabled(&failed));
+        assert!(!triggers.is_disabled(&delivered));
+        drop(triggers);
+
+        harness.send("/setquota 7 5").await;
+        assert_eq!(harness.limits.quota(ChatId(7)).await, 5);
+    }
+}
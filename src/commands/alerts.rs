@@ -0,0 +1,210 @@
+use crate::commands::{
+    callback::{AlertAction, Callback},
+    history::History,
+    price,
+    settings::Language,
+    triggers::{self, Target, Trigger},
+    Deps, HandlerResult, MyDialogue,
+};
+
+use anyhow::Context;
+use log::{info, warn};
+
+use chrono::{Duration, Utc};
+use rust_decimal::Decimal;
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use teloxide::{prelude::*, types::InlineKeyboardMarkup};
+
+const SNOOZE_HOURS: i64 = 1;
+const STEP_PERCENT: price::Price = Decimal::from_parts(5, 0, 0, false, 0);
+
+pub fn keyboard(target: &Target, language: Language) -> InlineKeyboardMarkup {
+    let button =
+        |text: String, action: AlertAction| Callback::Alert(action, target.clone()).button(text);
+
+    InlineKeyboardMarkup::new([
+        vec![
+            button(
+                language.pick("Повторити", "Repeat").into(),
+                AlertAction::Rearm,
+            ),
+            button(
+                language.pick("Відкласти на 1 год", "Snooze for 1 h").into(),
+                AlertAction::Snooze,
+            ),
+        ],
+        vec![
+            button(format!("-{STEP_PERCENT}%"), AlertAction::Down),
+            button(format!("+{STEP_PERCENT}%"), AlertAction::Up),
+        ],
+        vec![button(
+            language.pick("Видалити", "Delete").into(),
+            AlertAction::Delete,
+        )],
+    ])
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    matches!(Callback::from_query(&q), Some(Callback::Alert(..)))
+}
+
+/// The fired trigger with its label and expiry, looked up in the history since fired triggers leave the store
+async fn fired_trigger(history: &Mutex<History>, chat_id: ChatId, target: Target) -> Trigger {
+    let locked_history = history.lock().await;
+    let entry = locked_history.last_fired(chat_id, &target);
+
+    Trigger {
+        label: entry.and_then(|x| x.label.clone()),
+        // A trigger restored after its expiry would only be swept away again
+        expires_at: entry.and_then(|x| x.expires_at).filter(|x| *x > Utc::now()),
+        ..Trigger::new(target)
+    }
+}
+
+pub async fn process(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    deps: Deps,
+    history: Arc<Mutex<History>>,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving alert action...");
+
+    bot.answer_callback_query(q.id.clone())
+        .await
+        .context("Failed to answer callback query")?;
+
+    let (Some(Callback::Alert(action, target)), Some(message)) =
+        (Callback::from_query(&q), q.message)
+    else {
+        warn!("Invalid alert callback query: {:?}", q.data);
+        return Ok(());
+    };
+
+    let chat_id = message.chat.id;
+
+    if !matches!(action, AlertAction::Delete) {
+        bot.edit_message_reply_markup(chat_id, message.id)
+            .await
+            .context("Failed to edit Telegram message")?;
+    }
+
+    match action {
+        AlertAction::Rearm => {
+            let trigger = fired_trigger(&history, chat_id, target).await;
+            triggers::add(bot, &dialogue, trigger, &deps, language).await?;
+        }
+        AlertAction::Up | AlertAction::Down => {
+            let step = if let AlertAction::Up = action {
+                STEP_PERCENT
+            } else {
+                -STEP_PERCENT
+            };
+
+            let mut trigger = fired_trigger(&history, chat_id, target).await;
+            let price = trigger.target.price();
+            trigger
+                .target
+                .set(price * (Decimal::ONE_HUNDRED + step) / Decimal::ONE_HUNDRED);
+
+            triggers::add(bot, &dialogue, trigger, &deps, language).await?;
+        }
+        AlertAction::Snooze => {
+            info!("Snoozing {target:?} trigger for chat {chat_id}");
+
+            let trigger = Trigger {
+                snoozed_until: Some(Utc::now() + Duration::hours(SNOOZE_HOURS)),
+                ..fired_trigger(&history, chat_id, target).await
+            };
+            triggers::add(bot, &dialogue, trigger, &deps, language).await?;
+        }
+        AlertAction::Delete => {
+            bot.delete_message(chat_id, message.id)
+                .await
+                .context("Failed to delete Telegram message")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::history::Entry;
+    use crate::testing::{Harness, CHAT_ID};
+
+    use rust_decimal_macros::dec;
+
+    /// Presses `action` on the alert of a labeled trigger which fired at 6.10$, returns the chat's triggers afterwards
+    async fn press(action: AlertAction) -> Vec<Trigger> {
+        let mut harness = Harness::new();
+        harness.set_price(dec!(6.1)).await;
+
+        harness.history.lock().await.record(
+            ChatId(CHAT_ID),
+            Entry {
+                target: Target::Lower(dec!(6.2)),
+                price: dec!(6.1),
+                fired_at: Utc::now(),
+                source: String::from(price::SOURCE),
+                label: Some(String::from("stop loss")),
+                expires_at: Some(Utc::now() + Duration::days(1)),
+            },
+        );
+
+        harness
+            .press(&Callback::Alert(action, Target::Lower(dec!(6.2))).encode())
+            .await;
+
+        let triggers = harness.triggers.lock().await;
+        triggers.get(&ChatId(CHAT_ID)).cloned().unwrap_or_default()
+    }
+
+    fn assert_restored(triggers: &[Trigger], target: Target) {
+        assert_eq!(triggers.len(), 1);
+        assert_eq!(triggers[0].target, target);
+        assert_eq!(triggers[0].label.as_deref(), Some("stop loss"));
+        assert!(triggers[0].expires_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn rearms_trigger() {
+        let triggers = press(AlertAction::Rearm).await;
+
+        assert_restored(&triggers, Target::Lower(dec!(6.2)));
+        // By default triggers wait for the engine to arm them
+        assert!(!triggers[0].armed);
+        assert!(triggers[0].snoozed_until.is_none());
+    }
+
+    #[tokio::test]
+    async fn shifts_trigger() {
+        let triggers = press(AlertAction::Up).await;
+        assert_restored(&triggers, Target::Lower(dec!(6.51)));
+
+        let triggers = press(AlertAction::Down).await;
+        assert_restored(&triggers, Target::Lower(dec!(5.89)));
+    }
+
+    #[tokio::test]
+    async fn snoozes_trigger() {
+        let triggers = press(AlertAction::Snooze).await;
+
+        assert_restored(&triggers, Target::Lower(dec!(6.2)));
+        assert!(triggers[0].armed);
+
+        let snoozed_until = triggers[0].snoozed_until.unwrap();
+        assert!(snoozed_until > Utc::now() + Duration::minutes(59));
+        assert!(snoozed_until <= Utc::now() + Duration::hours(SNOOZE_HOURS));
+    }
+
+    #[tokio::test]
+    async fn deletes_alert_without_adding_trigger() {
+        assert!(press(AlertAction::Delete).await.is_empty());
+    }
+}
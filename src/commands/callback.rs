@@ -0,0 +1,349 @@
+use crate::commands::{price, settings::Language, triggers::Target};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use teloxide::types::{CallbackQuery, InlineKeyboardButton};
+
+const VERSION: &str = "1";
+const SEPARATOR: &str = ":";
+
+/// Telegram rejects buttons with more than 64 bytes of callback data
+const MAX_LENGTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerKind {
+    Lower,
+    Higher,
+    ValueLower,
+    ValueHigher,
+    PnlLower,
+    PnlHigher,
+}
+
+impl TriggerKind {
+    pub fn target(self, price: price::Price) -> Target {
+        let mut target = match self {
+            Self::Lower => Target::Lower(price),
+            Self::Higher => Target::Higher(price),
+            Self::ValueLower => Target::ValueLower(price),
+            Self::ValueHigher => Target::ValueHigher(price),
+            Self::PnlLower => Target::PnlLower(price),
+            Self::PnlHigher => Target::PnlHigher(price),
+        };
+        target.set(price);
+
+        target
+    }
+
+    const fn code(self) -> &'static str {
+        match self {
+            Self::Lower => "l",
+            Self::Higher => "h",
+            Self::ValueLower => "vl",
+            Self::ValueHigher => "vh",
+            Self::PnlLower => "pl",
+            Self::PnlHigher => "ph",
+        }
+    }
+
+    fn decode(code: &str) -> Result<Self> {
+        match code {
+            "l" => Ok(Self::Lower),
+            "h" => Ok(Self::Higher),
+            "vl" => Ok(Self::ValueLower),
+            "vh" => Ok(Self::ValueHigher),
+            "pl" => Ok(Self::PnlLower),
+            "ph" => Ok(Self::PnlHigher),
+            _ => bail!("Unknown trigger kind: {code}"),
+        }
+    }
+}
+
+impl From<&Target> for TriggerKind {
+    fn from(target: &Target) -> Self {
+        match target {
+            Target::Lower(_) => Self::Lower,
+            Target::Higher(_) => Self::Higher,
+            Target::ValueLower(_) => Self::ValueLower,
+            Target::ValueHigher(_) => Self::ValueHigher,
+            Target::PnlLower(_) => Self::PnlLower,
+            Target::PnlHigher(_) => Self::PnlHigher,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertAction {
+    Rearm,
+    Snooze,
+    Up,
+    Down,
+    Delete,
+}
+
+impl AlertAction {
+    const fn code(self) -> &'static str {
+        match self {
+            Self::Rearm => "r",
+            Self::Snooze => "s",
+            Self::Up => "u",
+            Self::Down => "d",
+            Self::Delete => "x",
+        }
+    }
+
+    fn decode(code: &str) -> Result<Self> {
+        match code {
+            "r" => Ok(Self::Rearm),
+            "s" => Ok(Self::Snooze),
+            "u" => Ok(Self::Up),
+            "d" => Ok(Self::Down),
+            "x" => Ok(Self::Delete),
+            _ => bail!("Unknown alert action: {code}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingsAction {
+    ToggleQuietHours,
+    StartDown,
+    StartUp,
+    EndDown,
+    EndUp,
+    ToggleMode,
+    CycleWrongSide,
+    ToggleLanguage,
+}
+
+impl SettingsAction {
+    const fn code(self) -> &'static str {
+        match self {
+            Self::ToggleQuietHours => "q",
+            Self::StartDown => "s-",
+            Self::StartUp => "s+",
+            Self::EndDown => "e-",
+            Self::EndUp => "e+",
+            Self::ToggleMode => "m",
+            Self::CycleWrongSide => "w",
+            Self::ToggleLanguage => "l",
+        }
+    }
+
+    fn decode(code: &str) -> Result<Self> {
+        match code {
+            "q" => Ok(Self::ToggleQuietHours),
+            "s-" => Ok(Self::StartDown),
+            "s+" => Ok(Self::StartUp),
+            "e-" => Ok(Self::EndDown),
+            "e+" => Ok(Self::EndUp),
+            "m" => Ok(Self::ToggleMode),
+            "w" => Ok(Self::CycleWrongSide),
+            "l" => Ok(Self::ToggleLanguage),
+            _ => bail!("Unknown settings action: {code}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Callback {
+    TriggerType(TriggerKind),
+    NoExpiry,
+    NoLabel,
+    DeleteTrigger(Target),
+    Alert(AlertAction, Target),
+    Settings(SettingsAction),
+    QuickTrigger(Target),
+    ConfirmConvert,
+    CancelConvert,
+    Language(Language),
+}
+
+impl Callback {
+    pub fn encode(&self) -> String {
+        let fields = match self {
+            Self::TriggerType(kind) => vec!["t", kind.code()],
+            Self::NoExpiry => vec!["n"],
+            Self::NoLabel => vec!["l"],
+            Self::DeleteTrigger(target) => vec!["d", TriggerKind::from(target).code()],
+            Self::Alert(action, target) => {
+                vec!["a", action.code(), TriggerKind::from(target).code()]
+            }
+            Self::Settings(action) => vec!["s", action.code()],
+            Self::QuickTrigger(target) => vec!["q", TriggerKind::from(target).code()],
+            Self::ConfirmConvert => vec!["c"],
+            Self::CancelConvert => vec!["x"],
+            Self::Language(language) => vec!["g", language.code()],
+        };
+
+        let mut data = format!("{VERSION}{SEPARATOR}{}", fields.join(SEPARATOR));
+
+        if let Self::DeleteTrigger(target) | Self::Alert(_, target) | Self::QuickTrigger(target) =
+            self
+        {
+            data.push_str(SEPARATOR);
+            data.push_str(&target.price().normalize().to_string());
+        }
+
+        debug_assert!(
+            data.len() <= MAX_LENGTH,
+            "Callback data is too long: {data}"
+        );
+
+        data
+    }
+
+    pub fn decode(data: &str) -> Result<Self> {
+        let mut fields = data.split(SEPARATOR);
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Truncated callback data"))
+        };
+
+        let version = next()?;
+        if version != VERSION {
+            bail!("Unsupported callback data version: {version}");
+        }
+
+        let callback = match next()? {
+            "t" => Self::TriggerType(TriggerKind::decode(next()?)?),
+            "n" => Self::NoExpiry,
+            "l" => Self::NoLabel,
+            "d" => {
+                let kind = TriggerKind::decode(next()?)?;
+                Self::DeleteTrigger(kind.target(decode_price(next()?)?))
+            }
+            "a" => {
+                let action = AlertAction::decode(next()?)?;
+                let kind = TriggerKind::decode(next()?)?;
+                Self::Alert(action, kind.target(decode_price(next()?)?))
+            }
+            "s" => Self::Settings(SettingsAction::decode(next()?)?),
+            "q" => {
+                let kind = TriggerKind::decode(next()?)?;
+                Self::QuickTrigger(kind.target(decode_price(next()?)?))
+            }
+            "c" => Self::ConfirmConvert,
+            "x" => Self::CancelConvert,
+            "g" => {
+                let code = next()?;
+                Self::Language(
+                    Language::decode(code).ok_or_else(|| anyhow!("Unknown language: {code}"))?,
+                )
+            }
+            tag => bail!("Unknown callback tag: {tag}"),
+        };
+
+        if fields.next().is_some() {
+            bail!("Trailing callback data: {data}");
+        }
+
+        Ok(callback)
+    }
+
+    pub fn from_query(q: &CallbackQuery) -> Option<Self> {
+        q.data.as_deref().and_then(|data| Self::decode(data).ok())
+    }
+
+    pub fn button(&self, text: impl Into<String>) -> InlineKeyboardButton {
+        InlineKeyboardButton::callback(text, self.encode())
+    }
+}
+
+fn decode_price(price: &str) -> Result<price::Price> {
+    price
+        .parse::<price::Price>()
+        .with_context(|| format!("Invalid price in callback data: {price}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rust_decimal_macros::dec;
+
+    fn all_callbacks() -> Vec<Callback> {
+        let mut callbacks = vec![
+            Callback::TriggerType(TriggerKind::Lower),
+            Callback::TriggerType(TriggerKind::Higher),
+            Callback::NoExpiry,
+            Callback::NoLabel,
+            Callback::ConfirmConvert,
+            Callback::CancelConvert,
+            Callback::Language(Language::Ukrainian),
+            Callback::Language(Language::English),
+        ];
+
+        for action in [
+            SettingsAction::ToggleQuietHours,
+            SettingsAction::StartDown,
+            SettingsAction::StartUp,
+            SettingsAction::EndDown,
+            SettingsAction::EndUp,
+            SettingsAction::ToggleMode,
+            SettingsAction::CycleWrongSide,
+            SettingsAction::ToggleLanguage,
+        ] {
+            callbacks.push(Callback::Settings(action));
+        }
+
+        for target in [
+            Target::Lower(dec!(6.2)),
+            Target::Higher(dec!(0.3)),
+            Target::Higher(dec!(123_456.789)),
+            Target::Lower(dec!(0.001)),
+            Target::ValueHigher(dec!(1_250.5)),
+            Target::PnlLower(dec!(-12.5)),
+        ] {
+            callbacks.push(Callback::DeleteTrigger(target.clone()));
+            callbacks.push(Callback::QuickTrigger(target.clone()));
+
+            for action in [
+                AlertAction::Rearm,
+                AlertAction::Snooze,
+                AlertAction::Up,
+                AlertAction::Down,
+                AlertAction::Delete,
+            ] {
+                callbacks.push(Callback::Alert(action, target.clone()));
+            }
+        }
+
+        callbacks
+    }
+
+    #[test]
+    fn round_trip() {
+        for callback in all_callbacks() {
+            let data = callback.encode();
+
+            assert!(data.len() <= MAX_LENGTH, "{data} is too long");
+            assert_eq!(Callback::decode(&data).unwrap(), callback);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_data() {
+        for data in [
+            "",
+            "Lower",
+            "6.2",
+            "0:t:l",
+            "1:t",
+            "1:t:x",
+            "1:t:l:6.2",
+            "1:d:l",
+            "1:d:l:abc",
+            "1:a:q:l:6.2",
+            "1:n:l",
+            "1:l:x",
+            "1:g:de",
+            "1:s:o",
+            "1:q:h",
+            "1:z:l",
+        ] {
+            assert!(Callback::decode(data).is_err(), "{data} was accepted");
+        }
+    }
+}
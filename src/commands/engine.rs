@@ -0,0 +1,497 @@
+//! Trigger evaluation without any I/O: given the triggers and a quote it decides what fires,
+//! callers apply the resulting events and deliver notifications
+
+use crate::commands::{
+    portfolio::Portfolio,
+    price,
+    store::TriggerStore,
+    triggers::{Target, Trigger},
+};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Bound;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<K> {
+    /// The trigger's threshold was reached and the trigger is disarmed
+    Fired {
+        chat_id: K,
+        target: Target,
+        /// Value of the quantity watched by the trigger, e.g. the price or portfolio P&L
+        observed: Decimal,
+    },
+    /// The trigger outlived its expiry without firing
+    Expired { chat_id: K, target: Target },
+    /// The price moved away from the threshold of a trigger waiting for a crossing,
+    /// so the trigger fires the next time the threshold is reached
+    Armed { chat_id: K, target: Target },
+}
+
+impl<K: Copy> Event<K> {
+    pub const fn chat_id(&self) -> K {
+        match self {
+            Self::Fired { chat_id, .. }
+            | Self::Expired { chat_id, .. }
+            | Self::Armed { chat_id, .. } => *chat_id,
+        }
+    }
+
+    pub const fn target(&self) -> &Target {
+        match self {
+            Self::Fired { target, .. }
+            | Self::Expired { target, .. }
+            | Self::Armed { target, .. } => target,
+        }
+    }
+}
+
+/// Current value of the quantity watched by the trigger,
+/// `None` for portfolio triggers of a chat without positions
+pub fn observe(
+    target: &Target,
+    price: price::Price,
+    portfolio: Option<&Portfolio>,
+) -> Option<Decimal> {
+    match target {
+        Target::Lower(_) | Target::Higher(_) => Some(price),
+        Target::ValueLower(_) | Target::ValueHigher(_) => portfolio
+            .filter(|x| !x.positions.is_empty())
+            .map(|x| x.value(price)),
+        Target::PnlLower(_) | Target::PnlHigher(_) => portfolio?.pnl_percent(price),
+    }
+}
+
+pub fn is_reached(target: &Target, observed: Decimal) -> bool {
+    if target.is_lower() {
+        observed <= target.price()
+    } else {
+        observed >= target.price()
+    }
+}
+
+/// Decides what happens to a single trigger at `price`
+fn event<K: Copy>(
+    chat_id: K,
+    trigger: &Trigger,
+    portfolio: Option<&Portfolio>,
+    price: price::Price,
+    now: DateTime<Utc>,
+) -> Option<Event<K>> {
+    if trigger.is_expired(now) || trigger.is_snoozed(now) {
+        return None;
+    }
+
+    let observed = observe(&trigger.target, price, portfolio)?;
+    let target = trigger.target.clone();
+
+    match (trigger.armed, is_reached(&trigger.target, observed)) {
+        (true, true) => Some(Event::Fired {
+            chat_id,
+            target,
+            observed,
+        }),
+        (false, false) => Some(Event::Armed { chat_id, target }),
+        _ => None,
+    }
+}
+
+/// Returns a `Fired` event for every unexpired armed trigger reached at `price`
+/// and an `Armed` event for every unarmed trigger which is not, snoozed triggers and
+/// disabled chats are skipped.
+/// Only price triggers on the affected side of `price` and portfolio triggers are visited
+pub fn evaluate<K: Copy + Eq + Hash>(
+    store: &TriggerStore<K>,
+    portfolios: &HashMap<K, Portfolio>,
+    price: price::Price,
+    now: DateTime<Utc>,
+) -> Vec<Event<K>> {
+    store
+        .price_triggers(true, true, price..)
+        .chain(store.price_triggers(false, true, ..=price))
+        .chain(store.price_triggers(true, false, ..price))
+        .chain(store.price_triggers(false, false, (Bound::Excluded(price), Bound::Unbounded)))
+        .chain(store.portfolio_triggers())
+        .filter(|(chat_id, _)| !store.is_disabled(chat_id))
+        .filter_map(|(chat_id, trigger)| {
+            event(chat_id, trigger, portfolios.get(&chat_id), price, now)
+        })
+        .collect()
+}
+
+/// Same as `evaluate`, but visits every trigger
+pub fn scan<K: Copy + Eq + Hash>(
+    store: &TriggerStore<K>,
+    portfolios: &HashMap<K, Portfolio>,
+    price: price::Price,
+    now: DateTime<Utc>,
+) -> Vec<Event<K>> {
+    store
+        .iter()
+        .filter(|(chat_id, _)| !store.is_disabled(chat_id))
+        .flat_map(|(chat_id, triggers)| triggers.iter().map(move |x| (*chat_id, x)))
+        .filter_map(|(chat_id, trigger)| {
+            event(chat_id, trigger, portfolios.get(&chat_id), price, now)
+        })
+        .collect()
+}
+
+/// Returns an `Expired` event for every trigger expired by `now`
+pub fn expire<K: Copy + Eq + Hash>(store: &TriggerStore<K>, now: DateTime<Utc>) -> Vec<Event<K>> {
+    store
+        .iter()
+        .flat_map(|(chat_id, triggers)| {
+            triggers
+                .iter()
+                .filter(|x| x.is_expired(now))
+                .map(|x| Event::Expired {
+                    chat_id: *chat_id,
+                    target: x.target.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Removes fired and expired triggers and arms the ones which saw the price on the other side
+pub fn apply<K: Copy + Eq + Hash>(store: &mut TriggerStore<K>, events: &[Event<K>]) {
+    for event in events {
+        match event {
+            Event::Fired {
+                chat_id, target, ..
+            }
+            | Event::Expired { chat_id, target } => {
+                store.remove(*chat_id, target);
+            }
+            Event::Armed { chat_id, target } => store.arm(*chat_id, target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::portfolio::Position;
+
+    use chrono::Duration;
+    use proptest::prelude::*;
+    use rust_decimal_macros::dec;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn triggers(targets: Vec<Target>) -> TriggerStore<u8> {
+        TriggerStore::from_iter([(1, targets.into_iter().map(Trigger::new).collect())])
+    }
+
+    fn portfolio(amount: Decimal, entry_price: Decimal) -> HashMap<u8, Portfolio> {
+        HashMap::from([(
+            1,
+            Portfolio {
+                positions: vec![Position {
+                    amount,
+                    entry_price,
+                    opened_at: now(),
+                }],
+            },
+        )])
+    }
+
+    #[test]
+    fn fires_price_triggers_at_threshold() {
+        let triggers = triggers(vec![
+            Target::Lower(dec!(6)),
+            Target::Lower(dec!(5)),
+            Target::Higher(dec!(6)),
+            Target::Higher(dec!(7)),
+        ]);
+
+        let events = evaluate(&triggers, &HashMap::new(), dec!(6), now());
+
+        assert_eq!(events.len(), 2);
+        assert!(events.contains(&Event::Fired {
+            chat_id: 1,
+            target: Target::Lower(dec!(6)),
+            observed: dec!(6),
+        }));
+        assert!(events.contains(&Event::Fired {
+            chat_id: 1,
+            target: Target::Higher(dec!(6)),
+            observed: dec!(6),
+        }));
+    }
+
+    #[test]
+    fn waits_for_crossing_before_firing() {
+        let mut triggers = TriggerStore::default();
+        triggers.insert(
+            1,
+            Trigger {
+                armed: false,
+                ..Trigger::new(Target::Lower(dec!(7)))
+            },
+        );
+
+        assert!(evaluate(&triggers, &HashMap::new(), dec!(6), now()).is_empty());
+
+        let events = evaluate(&triggers, &HashMap::new(), dec!(7.5), now());
+        assert_eq!(
+            events,
+            vec![Event::Armed {
+                chat_id: 1,
+                target: Target::Lower(dec!(7)),
+            }]
+        );
+        apply(&mut triggers, &events);
+
+        assert!(triggers[&1][0].armed);
+        assert_eq!(
+            evaluate(&triggers, &HashMap::new(), dec!(6.9), now()).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn ignores_expired_triggers() {
+        let mut triggers = TriggerStore::default();
+        triggers.insert(
+            1,
+            Trigger {
+                expires_at: Some(now() - Duration::minutes(1)),
+                ..Trigger::new(Target::Lower(dec!(6)))
+            },
+        );
+
+        assert!(evaluate(&triggers, &HashMap::new(), dec!(5), now()).is_empty());
+        assert_eq!(
+            expire(&triggers, now()),
+            vec![Event::Expired {
+                chat_id: 1,
+                target: Target::Lower(dec!(6)),
+            }]
+        );
+    }
+
+    #[test]
+    fn skips_disabled_chats() {
+        let mut triggers = triggers(vec![Target::Lower(dec!(6)), Target::PnlHigher(dec!(10))]);
+        let portfolios = portfolio(dec!(10), dec!(4));
+        triggers.disable(1);
+
+        assert!(evaluate(&triggers, &portfolios, dec!(5), now()).is_empty());
+        assert!(scan(&triggers, &portfolios, dec!(5), now()).is_empty());
+
+        // Disabled chats survive a backup
+        let restored: TriggerStore<u8> =
+            bincode::deserialize(&bincode::serialize(&triggers).unwrap()).unwrap();
+        assert!(restored.is_disabled(&1));
+        assert_eq!(restored.trigger_count(), 2);
+
+        triggers.enable(&1);
+        assert_eq!(evaluate(&triggers, &portfolios, dec!(5), now()).len(), 2);
+    }
+
+    #[test]
+    fn ignores_snoozed_triggers() {
+        let mut triggers = TriggerStore::default();
+        triggers.insert(
+            1,
+            Trigger {
+                snoozed_until: Some(now() + Duration::hours(1)),
+                ..Trigger::new(Target::Lower(dec!(6)))
+            },
+        );
+        triggers.insert(
+            1,
+            Trigger {
+                armed: false,
+                snoozed_until: Some(now() + Duration::hours(1)),
+                ..Trigger::new(Target::Higher(dec!(7)))
+            },
+        );
+
+        assert!(evaluate(&triggers, &HashMap::new(), dec!(5), now()).is_empty());
+
+        let later = now() + Duration::hours(1);
+        assert_eq!(
+            evaluate(&triggers, &HashMap::new(), dec!(5), later),
+            vec![
+                Event::Fired {
+                    chat_id: 1,
+                    target: Target::Lower(dec!(6)),
+                    observed: dec!(5),
+                },
+                Event::Armed {
+                    chat_id: 1,
+                    target: Target::Higher(dec!(7)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fires_portfolio_triggers() {
+        let triggers = triggers(vec![
+            Target::ValueHigher(dec!(700)),
+            Target::PnlHigher(dec!(10)),
+            Target::PnlLower(dec!(-10)),
+        ]);
+        let portfolios = portfolio(dec!(100), dec!(6));
+
+        let events = evaluate(&triggers, &portfolios, dec!(7), now());
+
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|x| !x.target().is_lower()));
+    }
+
+    #[test]
+    fn skips_portfolio_triggers_without_positions() {
+        let triggers = triggers(vec![
+            Target::ValueLower(dec!(1000)),
+            Target::PnlLower(dec!(0)),
+        ]);
+
+        assert!(evaluate(&triggers, &HashMap::new(), dec!(1), now()).is_empty());
+    }
+
+    #[test]
+    fn apply_disarms_triggers() {
+        let mut triggers = triggers(vec![Target::Lower(dec!(6)), Target::Higher(dec!(7))]);
+
+        let events = evaluate(&triggers, &HashMap::new(), dec!(5), now());
+        apply(&mut triggers, &events);
+
+        assert_eq!(triggers[&1].len(), 1);
+        assert!(evaluate(&triggers, &HashMap::new(), dec!(5), now()).is_empty());
+
+        apply(
+            &mut triggers,
+            &[Event::Expired {
+                chat_id: 1,
+                target: Target::Higher(dec!(7)),
+            }],
+        );
+
+        assert!(triggers.is_empty());
+    }
+
+    /// Prices between 0.001 and 100 in exchange ticks
+    fn price() -> impl Strategy<Value = Decimal> {
+        (1..100_000_i64).prop_map(|x| Decimal::new(x, 3))
+    }
+
+    fn target() -> impl Strategy<Value = Target> {
+        (0..2_u8, price()).prop_map(|(kind, price)| {
+            if kind == 0 {
+                Target::Lower(price)
+            } else {
+                Target::Higher(price)
+            }
+        })
+    }
+
+    /// Triggers of a few chats in any arming state, including portfolio triggers
+    fn store() -> impl Strategy<Value = TriggerStore<u8>> {
+        let trigger = (0..4_u8, price(), any::<bool>()).prop_map(|(kind, price, armed)| {
+            let target = match kind {
+                0 => Target::Lower(price),
+                1 => Target::Higher(price),
+                2 => Target::ValueLower(price * dec!(100)),
+                _ => Target::PnlHigher(price - dec!(50)),
+            };
+
+            Trigger {
+                armed,
+                ..Trigger::new(target)
+            }
+        });
+
+        prop::collection::vec((0..5_u8, trigger), 0..50).prop_map(|triggers| {
+            let mut store = TriggerStore::default();
+
+            for (chat_id, trigger) in triggers {
+                store.insert(chat_id, trigger);
+            }
+
+            store
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn index_matches_scan(
+            mut store in store(),
+            prices in prop::collection::vec(price(), 1..10),
+        ) {
+            let portfolios = (0..5_u8)
+                .flat_map(|chat_id| portfolio(dec!(10), dec!(5)).into_values().map(move |x| (chat_id, x)))
+                .collect::<HashMap<_, _>>();
+
+            for price in prices {
+                let events = evaluate(&store, &portfolios, price, now());
+                let scanned = scan(&store, &portfolios, price, now());
+
+                prop_assert_eq!(events.len(), scanned.len());
+                prop_assert!(events.iter().all(|x| scanned.contains(x)));
+
+                apply(&mut store, &events);
+            }
+        }
+
+        #[test]
+        fn fires_exactly_reached_triggers(
+            targets in prop::collection::vec(target(), 0..20),
+            price in price(),
+        ) {
+            let triggers = triggers(targets.clone());
+            let events = evaluate(&triggers, &HashMap::new(), price, now());
+
+            // The store keeps a single trigger per target
+            let expected = targets
+                .iter()
+                .enumerate()
+                .filter(|(i, x)| !targets[..*i].contains(x))
+                .map(|(_, x)| x)
+                .filter(|x| match x {
+                    Target::Lower(level) => price <= *level,
+                    _ => price >= x.price(),
+                })
+                .count();
+
+            prop_assert_eq!(events.len(), expected);
+        }
+
+        #[test]
+        fn fired_triggers_do_not_fire_twice(
+            targets in prop::collection::vec(target(), 0..20),
+            price in price(),
+        ) {
+            let mut triggers = triggers(targets);
+
+            let events = evaluate(&triggers, &HashMap::new(), price, now());
+            apply(&mut triggers, &events);
+
+            prop_assert!(evaluate(&triggers, &HashMap::new(), price, now()).is_empty());
+        }
+
+        #[test]
+        fn evaluation_does_not_depend_on_order(
+            targets in prop::collection::vec(target(), 0..20),
+            price in price(),
+        ) {
+            let mut reversed = targets.clone();
+            reversed.reverse();
+
+            let events = evaluate(&triggers(targets), &HashMap::new(), price, now());
+            let reversed_events = evaluate(&triggers(reversed), &HashMap::new(), price, now());
+
+            prop_assert_eq!(events.len(), reversed_events.len());
+            prop_assert!(events.iter().all(|x| reversed_events.contains(x)));
+        }
+    }
+}
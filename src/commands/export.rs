@@ -0,0 +1,618 @@
+use crate::commands::{
+    callback::TriggerKind,
+    price,
+    settings::{self, Language},
+    store::TriggerStore,
+    triggers, Deps, HandlerResult, MyDialogue, State,
+};
+
+use anyhow::{bail, Context, Result};
+use log::{error, info, warn};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use teloxide::{net::Download, prelude::*, types::InputFile};
+
+const FORMAT_VERSION: u32 = 1;
+const MAX_FILE_SIZE: u32 = 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    Lower,
+    Higher,
+}
+
+/// Quantity watched by the trigger
+#[derive(Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Subject {
+    #[default]
+    Price,
+    Value,
+    Pnl,
+}
+
+impl Subject {
+    fn is_price(&self) -> bool {
+        *self == Self::Price
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedTrigger {
+    #[serde(default, skip_serializing_if = "Subject::is_price")]
+    subject: Subject,
+    direction: Direction,
+    /// Written as a JSON number so exports stay compatible with float prices
+    #[serde(with = "rust_decimal::serde::float")]
+    price: price::Price,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    /// Unarmed triggers wait for the threshold to be crossed before they can fire
+    #[serde(default = "default_armed")]
+    armed: bool,
+}
+
+/// Files exported before `armed` was written only had triggers ready to fire
+const fn default_armed() -> bool {
+    true
+}
+
+impl From<&triggers::Trigger> for ExportedTrigger {
+    fn from(trigger: &triggers::Trigger) -> Self {
+        let subject = match trigger.target {
+            triggers::Target::Lower(_) | triggers::Target::Higher(_) => Subject::Price,
+            triggers::Target::ValueLower(_) | triggers::Target::ValueHigher(_) => Subject::Value,
+            triggers::Target::PnlLower(_) | triggers::Target::PnlHigher(_) => Subject::Pnl,
+        };
+        let direction = if trigger.target.is_lower() {
+            Direction::Lower
+        } else {
+            Direction::Higher
+        };
+
+        Self {
+            subject,
+            direction,
+            price: trigger.target.price(),
+            expires_at: trigger.expires_at,
+            label: trigger.label.clone(),
+            armed: trigger.armed,
+        }
+    }
+}
+
+impl ExportedTrigger {
+    fn into_trigger(self, now: DateTime<Utc>, language: Language) -> Result<triggers::Trigger> {
+        let kind = match (self.subject, self.direction) {
+            (Subject::Price, Direction::Lower) => TriggerKind::Lower,
+            (Subject::Price, Direction::Higher) => TriggerKind::Higher,
+            (Subject::Value, Direction::Lower) => TriggerKind::ValueLower,
+            (Subject::Value, Direction::Higher) => TriggerKind::ValueHigher,
+            (Subject::Pnl, Direction::Lower) => TriggerKind::PnlLower,
+            (Subject::Pnl, Direction::Higher) => TriggerKind::PnlHigher,
+        };
+        let target = kind.target(self.price);
+
+        if !target.is_valid() {
+            bail!(
+                "{} {}",
+                language.pick("некоректне значення", "invalid value"),
+                self.price
+            );
+        }
+
+        if self.expires_at.is_some_and(|x| x <= now) {
+            bail!(language.pick("термін дії вже минув", "already expired"));
+        }
+
+        let label = match self.label {
+            Some(label) => Some(triggers::parse_label(&label).with_context(|| {
+                format!(
+                    "{} «{label}»",
+                    language.pick("некоректний підпис", "invalid label")
+                )
+            })?),
+            None => None,
+        };
+
+        Ok(triggers::Trigger {
+            expires_at: self.expires_at,
+            label,
+            armed: self.armed,
+            ..triggers::Trigger::new(target)
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportedSettings {
+    time_zone: Tz,
+    quiet_hours: bool,
+    quiet_start: u8,
+    quiet_end: u8,
+    quiet_mode: settings::QuietMode,
+    #[serde(default)]
+    wrong_side: settings::WrongSide,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Export {
+    version: u32,
+    /// Kept as raw values so that a single malformed trigger doesn't reject the whole file
+    triggers: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    settings: Option<ExportedSettings>,
+}
+
+/// Contents of an exported file, every trigger either parsed or with the reason it was rejected
+pub struct Parsed {
+    pub triggers: Vec<Result<triggers::Trigger>>,
+    settings: Option<ExportedSettings>,
+}
+
+impl Parsed {
+    /// Wrong side policy of the exported settings, the default one if the file has none
+    pub fn wrong_side(&self) -> settings::WrongSide {
+        self.settings
+            .as_ref()
+            .map(|x| x.wrong_side)
+            .unwrap_or_default()
+    }
+}
+
+/// Parses an exported file, used both by /import and by the replay. Errors are in `language`
+pub fn parse(content: &[u8], now: DateTime<Utc>, language: Language) -> Result<Parsed> {
+    let export = serde_json::from_slice::<Export>(content)
+        .context(language.pick("Некоректний файл", "Invalid file"))?;
+
+    if export.version != FORMAT_VERSION {
+        bail!(
+            "{}: {}",
+            language.pick("Непідтримувана версія файлу", "Unsupported file version"),
+            export.version
+        );
+    }
+
+    let triggers = export
+        .triggers
+        .into_iter()
+        .map(|value| {
+            serde_json::from_value::<ExportedTrigger>(value)
+                .map_err(anyhow::Error::from)
+                .and_then(|x| x.into_trigger(now, language))
+        })
+        .collect();
+
+    Ok(Parsed {
+        triggers,
+        settings: export.settings,
+    })
+}
+
+pub async fn export(
+    bot: Bot,
+    msg: Message,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    settings: Arc<Mutex<HashMap<ChatId, settings::Settings>>>,
+) -> HandlerResult {
+    info!("Exporting triggers...");
+
+    let exported_triggers = triggers
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .into_iter()
+        .flatten()
+        .map(|x| serde_json::to_value(ExportedTrigger::from(x)))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to serialize triggers")?;
+
+    let exported_settings = settings
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .map(|x| ExportedSettings {
+            time_zone: x.time_zone,
+            quiet_hours: x.quiet_hours,
+            quiet_start: x.quiet_start,
+            quiet_end: x.quiet_end,
+            quiet_mode: x.quiet_mode,
+            wrong_side: x.wrong_side,
+        });
+
+    let content = serde_json::to_vec_pretty(&Export {
+        version: FORMAT_VERSION,
+        triggers: exported_triggers,
+        settings: exported_settings,
+    })
+    .context("Failed to serialize export")?;
+
+    bot.send_document(
+        msg.chat.id,
+        InputFile::memory(content).file_name("near-triggers.json"),
+    )
+    .await
+    .context("Failed to send Telegram document")?;
+
+    Ok(())
+}
+
+pub async fn start_import(bot: Bot, dialogue: MyDialogue, language: Language) -> HandlerResult {
+    bot.send_message(
+        dialogue.chat_id(),
+        language.pick(
+            "Надішліть файл, отриманий командою /export:",
+            "Send the file you got from /export:",
+        ),
+    )
+    .await
+    .context("Failed to send Telegram message")?;
+
+    dialogue
+        .update(State::ReceiveImport)
+        .await
+        .context("Failed to update state")?;
+
+    Ok(())
+}
+
+pub async fn receive_import(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    deps: Deps,
+    language: Language,
+) -> HandlerResult {
+    info!("Importing triggers...");
+
+    let Some(document) = msg.document() else {
+        bot.send_message(
+            msg.chat.id,
+            language.pick("Надішліть файл у форматі JSON:", "Send a JSON file:"),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    };
+
+    dialogue.exit().await.context("Failed to reset state")?;
+
+    if document.file.size > MAX_FILE_SIZE {
+        bot.send_message(
+            msg.chat.id,
+            language.pick("Файл занадто великий", "The file is too large"),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    }
+
+    let file = bot
+        .get_file(&document.file.id)
+        .await
+        .context("Failed to get Telegram file")?;
+
+    let mut content = Vec::new();
+    bot.download_file(&file.path, &mut content)
+        .await
+        .context("Failed to download Telegram file")?;
+
+    let summary = match parse(&content, Utc::now(), language) {
+        Ok(parsed) => import(parsed, msg.chat.id, &deps, language).await,
+        Err(err) => {
+            warn!("Invalid import file: {err:#}");
+            format!("{err:#}")
+        }
+    };
+
+    bot.send_message(msg.chat.id, summary)
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+/// Adds the parsed triggers and settings to the chat, returns the summary shown to it
+async fn import(parsed: Parsed, chat_id: ChatId, deps: &Deps, language: Language) -> String {
+    let (mut imported, mut duplicates, mut rejected) = (0, 0, Vec::new());
+
+    // Imported triggers go through the same wrong side policy as added ones, but a batch can't ask
+    // for a confirmation per trigger, so triggers the chat would have to convert are rejected
+    let mut accepted = Vec::new();
+
+    for (index, trigger) in parsed.triggers.into_iter().enumerate() {
+        let reason = match trigger {
+            Ok(trigger) => match triggers::arming(trigger.clone(), chat_id, deps).await {
+                triggers::Arming::Insert(trigger) => {
+                    accepted.push((index, trigger));
+                    continue;
+                }
+                triggers::Arming::Reject(observed) => format!(
+                    "{}, {}",
+                    triggers::describe_observed(&trigger.target, observed, language),
+                    language.pick(
+                        "тригер спрацював би одразу",
+                        "the trigger would fire at once"
+                    )
+                ),
+                triggers::Arming::Convert(trigger, observed) => format!(
+                    "{}, {}",
+                    triggers::describe_observed(&trigger.target, observed, language),
+                    language.pick(
+                        "змінити напрямок тригера можна лише через /addtrigger",
+                        "the trigger's direction can only be flipped with /addtrigger"
+                    )
+                ),
+            },
+            Err(err) => err.to_string(),
+        };
+
+        rejected.push((index, reason));
+    }
+
+    {
+        let mut locked_triggers = deps.triggers.lock().await;
+
+        for (index, trigger) in accepted {
+            if let Err(exceeded) = deps.limits.check(&locked_triggers, chat_id).await {
+                rejected.push((index, exceeded.describe(language)));
+            } else if locked_triggers.insert(chat_id, trigger) {
+                imported += 1;
+            } else {
+                duplicates += 1;
+            }
+        }
+
+        if let Err(err) = triggers::backup(&deps.storage, &locked_triggers) {
+            error!("Failed to backup triggers, due to: {}", err);
+        }
+    }
+
+    let mut summary = match language {
+        Language::Ukrainian => format!(
+            "Імпортовано тригерів: {imported}\nДублікатів пропущено: {duplicates}\nВідхилено: {}",
+            rejected.len()
+        ),
+        Language::English => format!(
+            "Triggers imported: {imported}\nDuplicates skipped: {duplicates}\nRejected: {}",
+            rejected.len()
+        ),
+    };
+
+    rejected.sort_by_key(|(index, _)| *index);
+
+    for (index, reason) in &rejected {
+        summary.push_str(&format!("\n#{}: {reason}", index + 1));
+    }
+
+    if let Some(exported) = parsed.settings {
+        let mut locked_settings = deps.settings.lock().await;
+        let chat_settings = settings::chat_entry(&mut locked_settings, chat_id, language);
+
+        let mut imported_settings = settings::Settings {
+            time_zone: exported.time_zone,
+            quiet_hours: exported.quiet_hours,
+            quiet_start: exported.quiet_start,
+            quiet_end: exported.quiet_end,
+            quiet_mode: exported.quiet_mode,
+            deferred: Vec::new(),
+            dropped: 0,
+            wrong_side: exported.wrong_side,
+            // The chat keeps the language it chose, its command menu is in that language
+            language: chat_settings.language,
+        };
+
+        if imported_settings.is_valid() {
+            imported_settings.deferred = std::mem::take(&mut chat_settings.deferred);
+            imported_settings.dropped = chat_settings.dropped;
+            *chat_settings = imported_settings;
+
+            summary.push_str(language.pick("\nНалаштування імпортовано", "\nSettings imported"));
+        } else {
+            summary.push_str(language.pick(
+                "\nНалаштування відхилено: некоректні значення",
+                "\nSettings rejected: invalid values",
+            ));
+        }
+
+        if let Err(err) = settings::backup(&deps.storage, &locked_settings) {
+            error!("Failed to backup settings, due to: {}", err);
+        }
+    }
+
+    info!(
+        "Imported {imported} triggers for chat {chat_id}, {} rejected",
+        rejected.len()
+    );
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::settings::WrongSide;
+    use crate::testing::{Harness, CHAT_ID};
+
+    use rust_decimal_macros::dec;
+    use serde_json::{json, Value};
+
+    fn file(triggers: Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({ "version": FORMAT_VERSION, "triggers": triggers })).unwrap()
+    }
+
+    async fn import_file(harness: &Harness, content: &[u8]) -> String {
+        let parsed = parse(content, Utc::now(), Language::Ukrainian).unwrap();
+        import(
+            parsed,
+            ChatId(CHAT_ID),
+            &harness.deps(),
+            Language::Ukrainian,
+        )
+        .await
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let err = parse(b"{\"version\": 1", Utc::now(), Language::Ukrainian)
+            .err()
+            .unwrap();
+        assert!(format!("{err:#}").starts_with("Некоректний файл: "));
+
+        let content = serde_json::to_vec(&json!({ "version": 2, "triggers": [] })).unwrap();
+        let err = parse(&content, Utc::now(), Language::Ukrainian)
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Непідтримувана версія файлу: 2");
+    }
+
+    #[test]
+    fn round_trips_triggers() {
+        let trigger = triggers::Trigger {
+            label: Some(String::from("stop loss")),
+            armed: false,
+            ..triggers::Trigger::new(triggers::Target::PnlLower(dec!(-10)))
+        };
+
+        let value = serde_json::to_value(ExportedTrigger::from(&trigger)).unwrap();
+        let parsed = parse(&file(json!([value])), Utc::now(), Language::Ukrainian).unwrap();
+
+        let restored = parsed.triggers[0].as_ref().unwrap();
+        assert_eq!(restored.target, trigger.target);
+        assert_eq!(restored.label, trigger.label);
+        assert!(!restored.armed);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_triggers() {
+        let harness = Harness::new();
+
+        let summary = import_file(
+            &harness,
+            &file(json!([
+                { "direction": "lower", "price": -1 },
+                { "direction": "lower", "price": 0.0004 },
+                { "direction": "sideways", "price": 5 },
+                { "direction": "higher", "price": 7, "expires_at": "2020-01-01T00:00:00Z" },
+                { "direction": "higher", "price": 7, "label": "x".repeat(100) },
+                { "direction": "higher", "price": 8 },
+            ])),
+        )
+        .await;
+
+        let lines = summary.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..3],
+            [
+                "Імпортовано тригерів: 1",
+                "Дублікатів пропущено: 0",
+                "Відхилено: 5"
+            ]
+        );
+        assert_eq!(lines[3], "#1: некоректне значення -1");
+        assert_eq!(lines[4], "#2: некоректне значення 0.0004");
+        assert!(lines[5].starts_with("#3: unknown variant `sideways`"));
+        assert_eq!(lines[6], "#4: термін дії вже минув");
+        assert!(lines[7].starts_with("#5: некоректний підпис"));
+        assert_eq!(harness.triggers.lock().await.trigger_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn skips_duplicates() {
+        let harness = Harness::new();
+        harness.triggers.lock().await.insert(
+            ChatId(CHAT_ID),
+            triggers::Trigger::new(triggers::Target::Lower(dec!(5))),
+        );
+
+        let summary = import_file(
+            &harness,
+            &file(json!([
+                { "direction": "lower", "price": 5 },
+                { "direction": "higher", "price": 7 },
+                { "direction": "higher", "price": 7.0 },
+            ])),
+        )
+        .await;
+
+        assert!(
+            summary.starts_with("Імпортовано тригерів: 1\nДублікатів пропущено: 2\nВідхилено: 0")
+        );
+        assert_eq!(harness.triggers.lock().await.trigger_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn enforces_trigger_quota() {
+        let harness = Harness::new();
+        harness
+            .limits
+            .set_quota(ChatId(CHAT_ID), Some(1))
+            .await
+            .unwrap();
+
+        let summary = import_file(
+            &harness,
+            &file(json!([
+                { "direction": "higher", "price": 7 },
+                { "direction": "higher", "price": 8 },
+            ])),
+        )
+        .await;
+
+        assert_eq!(
+            summary,
+            "Імпортовано тригерів: 1\nДублікатів пропущено: 0\nВідхилено: 1\n\
+             #2: досягнуто ліміт тригерів чату (1)"
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_wrong_side_policy() {
+        let harness = Harness::new();
+        harness.set_price(dec!(6)).await;
+        harness.settings.lock().await.insert(
+            ChatId(CHAT_ID),
+            settings::Settings {
+                wrong_side: WrongSide::Reject,
+                ..settings::Settings::default()
+            },
+        );
+
+        let summary = import_file(
+            &harness,
+            &file(json!([
+                { "direction": "lower", "price": 7 },
+                { "direction": "lower", "price": 7.5, "armed": false },
+                { "direction": "higher", "price": 7 },
+            ])),
+        )
+        .await;
+
+        assert_eq!(
+            summary,
+            "Імпортовано тригерів: 2\nДублікатів пропущено: 0\nВідхилено: 1\n\
+             #1: Ціна вже 6.00$, тригер спрацював би одразу"
+        );
+
+        let triggers = harness.triggers.lock().await;
+        let triggers = &triggers[&ChatId(CHAT_ID)];
+        assert!(triggers
+            .iter()
+            .any(|x| x.target == triggers::Target::Lower(dec!(7.5)) && !x.armed));
+        assert!(triggers
+            .iter()
+            .any(|x| x.target == triggers::Target::Higher(dec!(7)) && x.armed));
+    }
+}
@@ -0,0 +1,237 @@
+use crate::commands::{notifier::Notifier, settings::Language};
+
+use log::{error, info, warn};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+use teloxide::types::ChatId;
+
+fn outage_message(language: Language) -> &'static str {
+    language.pick(
+        "Не вдається отримати ціну NEAR, тригери тимчасово не перевіряються. \
+         Ми повідомимо, коли все відновиться",
+        "The NEAR price can't be fetched, triggers aren't checked for now. \
+         We'll let you know once it's back",
+    )
+}
+
+fn recovery_message(language: Language) -> &'static str {
+    language.pick(
+        "Ціни знову оновлюються, тригери працюють у звичному режимі",
+        "Prices are updated again, triggers work as usual",
+    )
+}
+
+#[derive(Default)]
+struct Status {
+    last_success: Option<Instant>,
+    /// Chats told about the current outage, they are told about the recovery as well
+    degraded_chats: Option<HashMap<ChatId, Language>>,
+}
+
+/// Detects price feed outages and notifies chats with triggers and the operator once per outage
+#[derive(Clone)]
+pub struct FeedMonitor {
+    notifier: Notifier,
+    admin_chat_id: Option<ChatId>,
+    outage_after: Duration,
+    status: Arc<Mutex<Status>>,
+}
+
+impl FeedMonitor {
+    pub fn new(notifier: Notifier, admin_chat_id: Option<ChatId>, outage_after: Duration) -> Self {
+        Self {
+            notifier,
+            admin_chat_id,
+            outage_after,
+            status: Arc::default(),
+        }
+    }
+
+    /// Called when a fresh quote was received
+    pub async fn record_success(&self) {
+        let mut status = self.status.lock().await;
+        status.last_success = Some(Instant::now());
+
+        let Some(chat_ids) = status.degraded_chats.take() else {
+            return;
+        };
+
+        info!("Price feed recovered");
+
+        self.broadcast(
+            chat_ids.into_iter().collect(),
+            recovery_message,
+            Some(String::from("Отримання цін відновлено")),
+        );
+    }
+
+    /// Called when no fresh quote could be obtained, with the chats whose triggers are affected and
+    /// their languages. Chats which got triggers after the outage was announced are told about it on the next call
+    pub async fn record_failure(&self, chats: impl IntoIterator<Item = (ChatId, Language)>) {
+        let mut status = self.status.lock().await;
+        let last_success = *status.last_success.get_or_insert_with(Instant::now);
+
+        if let Some(degraded_chats) = &mut status.degraded_chats {
+            let new_chats = chats
+                .into_iter()
+                .filter(|(chat_id, language)| degraded_chats.insert(*chat_id, *language).is_none())
+                .collect::<Vec<_>>();
+
+            if !new_chats.is_empty() {
+                self.broadcast(new_chats, outage_message, None);
+            }
+
+            return;
+        }
+
+        if last_success.elapsed() < self.outage_after {
+            return;
+        }
+
+        warn!(
+            "Price feed is degraded, no fresh quote for {:?}",
+            last_success.elapsed()
+        );
+
+        let chats = chats.into_iter().collect::<Vec<_>>();
+        status.degraded_chats = Some(chats.iter().copied().collect());
+
+        self.broadcast(
+            chats,
+            outage_message,
+            Some(format!(
+                "Немає актуальної ціни вже {} с",
+                last_success.elapsed().as_secs()
+            )),
+        );
+    }
+
+    /// Sends notices in the background so that trigger processing isn't held up by rate limits
+    fn broadcast(
+        &self,
+        chats: Vec<(ChatId, Language)>,
+        message: fn(Language) -> &'static str,
+        admin_message: Option<String>,
+    ) {
+        let notifier = self.notifier.clone();
+        let admin_chat_id = self.admin_chat_id.filter(|_| admin_message.is_some());
+
+        tokio::spawn(async move {
+            if let (Some(chat_id), Some(admin_message)) = (admin_chat_id, admin_message) {
+                if let Err(err) = notifier.send(chat_id, &admin_message, None, false).await {
+                    error!("Failed to notify admin chat about price feed: {err}");
+                }
+            }
+
+            for (chat_id, language) in chats {
+                if let Err(err) = notifier.send(chat_id, message(language), None, false).await {
+                    error!("Failed to notify chat {chat_id} about price feed: {err}");
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Harness, CHAT_ID};
+
+    const ADMIN_CHAT_ID: ChatId = ChatId(7);
+    const OUTAGE_AFTER: Duration = Duration::from_millis(100);
+    const CHAT: (ChatId, Language) = (ChatId(CHAT_ID), Language::Ukrainian);
+
+    /// Waits for `count` notices and returns them with their chats, checking no more follow
+    async fn notices(harness: &Harness, count: usize) -> Vec<(i64, String)> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut notices = Vec::new();
+
+        while notices.len() < count {
+            assert!(Instant::now() < deadline, "Got only {notices:?}");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            notices.extend(harness.take_requests().await.into_iter().map(|x| {
+                (
+                    x.body["chat_id"].as_i64().unwrap(),
+                    String::from(x.text().unwrap()),
+                )
+            }));
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(harness.take_requests().await.is_empty());
+
+        notices
+    }
+
+    #[tokio::test]
+    async fn notifies_once_per_outage() {
+        let harness = Harness::new();
+        let monitor = FeedMonitor::new(harness.notifier.clone(), Some(ADMIN_CHAT_ID), OUTAGE_AFTER);
+
+        // The first failure only starts the clock
+        monitor.record_failure([CHAT]).await;
+        assert!(notices(&harness, 0).await.is_empty());
+
+        tokio::time::sleep(OUTAGE_AFTER).await;
+        monitor.record_failure([CHAT]).await;
+        monitor
+            .record_failure([CHAT, (ChatId(8), Language::English)])
+            .await;
+
+        // A chat which added triggers during the outage is told about it as well
+        let mut degraded = notices(&harness, 3).await;
+        degraded.sort();
+
+        assert_eq!(degraded[0].0, ADMIN_CHAT_ID.0);
+        assert!(degraded[0].1.starts_with("Немає актуальної ціни вже"));
+        assert_eq!(degraded[1].0, 8);
+        assert!(degraded[1].1.starts_with("The NEAR price can't be fetched"));
+        assert_eq!(degraded[2].0, CHAT_ID);
+        assert!(degraded[2].1.starts_with("Не вдається отримати ціну NEAR"));
+
+        // Recovery is announced to the chats told about the outage
+        monitor.record_success().await;
+        monitor.record_success().await;
+
+        let mut recovered = notices(&harness, 3).await;
+        recovered.sort();
+
+        assert_eq!(
+            recovered,
+            [
+                (ADMIN_CHAT_ID.0, String::from("Отримання цін відновлено")),
+                (8, String::from(recovery_message(Language::English))),
+                (CHAT_ID, String::from(recovery_message(Language::Ukrainian))),
+            ]
+        );
+
+        // A new outage starts counting from the recovery
+        monitor.record_failure([CHAT]).await;
+        assert!(notices(&harness, 0).await.is_empty());
+
+        tokio::time::sleep(OUTAGE_AFTER).await;
+        monitor.record_failure([CHAT]).await;
+        assert_eq!(notices(&harness, 2).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn stays_quiet_without_an_outage() {
+        let harness = Harness::new();
+        let monitor = FeedMonitor::new(harness.notifier.clone(), None, OUTAGE_AFTER);
+
+        monitor.record_success().await;
+        monitor.record_failure([CHAT]).await;
+        monitor.record_success().await;
+
+        tokio::time::sleep(OUTAGE_AFTER).await;
+        monitor.record_success().await;
+        monitor.record_failure([CHAT]).await;
+
+        assert!(notices(&harness, 0).await.is_empty());
+    }
+}
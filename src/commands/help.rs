@@ -1,14 +1,14 @@
-use crate::commands::{Command, HandlerResult};
+use crate::commands::{menu, settings::Language, HandlerResult};
 
 use anyhow::Context;
 use log::info;
 
-use teloxide::{prelude::*, utils::command::BotCommands};
+use teloxide::prelude::*;
 
-pub async fn process(bot: Bot, msg: Message) -> HandlerResult {
+pub async fn process(bot: Bot, msg: Message, language: Language) -> HandlerResult {
     info!("Receiving help command...");
 
-    bot.send_message(msg.chat.id, Command::descriptions().to_string())
+    bot.send_message(msg.chat.id, menu::help(language))
         .await
         .context("Failed to send Telegram message")?;
 
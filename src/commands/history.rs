@@ -0,0 +1,275 @@
+use crate::commands::{
+    notifier, price,
+    settings::{self, Language},
+    storage::Storage,
+    triggers::Target,
+    HandlerResult,
+};
+use crate::config::HistoryConfig;
+
+use anyhow::{Context, Result};
+use log::info;
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use teloxide::prelude::*;
+
+const DEFAULT_SHOWN: usize = 10;
+/// Format version of history.bak
+const BACKUP_VERSION: u16 = 1;
+
+/// A single fired alert
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub target: Target,
+    /// Price which fired the trigger
+    pub price: price::Price,
+    pub fired_at: DateTime<Utc>,
+    /// Price feed the observed price came from
+    pub source: String,
+    /// Kept so actions on the alert can restore the trigger as it was
+    pub label: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Append-only log of fired alerts per chat, trimmed according to the retention limits
+pub struct History {
+    retention: HistoryConfig,
+    chats: HashMap<ChatId, VecDeque<Entry>>,
+}
+
+impl History {
+    pub fn new(retention: HistoryConfig) -> Self {
+        Self {
+            retention,
+            chats: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, chat_id: ChatId, entry: Entry) {
+        let now = entry.fired_at;
+
+        self.chats.entry(chat_id).or_default().push_back(entry);
+        self.prune(now);
+    }
+
+    /// Returns up to `n` most recent entries of the chat, newest first
+    pub fn recent(&self, chat_id: ChatId, n: usize) -> Vec<Entry> {
+        self.chats
+            .get(&chat_id)
+            .into_iter()
+            .flat_map(|entries| entries.iter().rev().take(n))
+            .cloned()
+            .collect()
+    }
+
+    /// Number of entries kept per chat
+    pub fn max_entries(&self) -> usize {
+        self.retention.max_entries
+    }
+
+    /// Most recent entry of the chat fired by a trigger with `target`
+    pub fn last_fired(&self, chat_id: ChatId, target: &Target) -> Option<&Entry> {
+        self.chats
+            .get(&chat_id)?
+            .iter()
+            .rev()
+            .find(|x| x.target.is_same(target))
+    }
+
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        let oldest = now - Duration::days(self.retention.max_age_days);
+        let max_entries = self.retention.max_entries;
+
+        for entries in self.chats.values_mut() {
+            while entries
+                .front()
+                .is_some_and(|x| x.fired_at < oldest || entries.len() > max_entries)
+            {
+                entries.pop_front();
+            }
+        }
+
+        self.chats.retain(|_, entries| !entries.is_empty());
+    }
+
+    pub fn backup(&self, storage: &Storage) -> Result<()> {
+        info!("Backing up history...");
+
+        storage.save("history.bak", BACKUP_VERSION, &self.chats)
+    }
+
+    pub fn restore(storage: &Storage, retention: HistoryConfig) -> Result<Self> {
+        info!("Restoring history...");
+
+        let chats = storage.load("history.bak", BACKUP_VERSION)?;
+        let mut history = Self { retention, chats };
+        history.prune(Utc::now());
+
+        Ok(history)
+    }
+}
+
+pub async fn show(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    history: Arc<Mutex<History>>,
+    settings: Arc<Mutex<HashMap<ChatId, settings::Settings>>>,
+    language: Language,
+) -> HandlerResult {
+    info!("Showing history...");
+
+    let args = args.trim();
+    let n = if args.is_empty() {
+        Some(DEFAULT_SHOWN)
+    } else {
+        args.parse::<usize>().ok().filter(|x| *x > 0)
+    };
+
+    let Some(n) = n else {
+        bot.send_message(
+            msg.chat.id,
+            language.pick("Формат: /history [кількість]", "Format: /history [count]"),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    };
+
+    let time_zone = settings
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .map_or(Tz::UTC, |x| x.time_zone);
+    let entries = {
+        let locked_history = history.lock().await;
+        // No more are kept anyway
+        let n = n.min(locked_history.max_entries());
+
+        locked_history.recent(msg.chat.id, n)
+    };
+
+    if entries.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            language.pick("Жоден тригер ще не спрацював", "No trigger has fired yet"),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    }
+
+    let header = format!(
+        "{} ({time_zone}):",
+        language.pick("Останні спрацювання", "Recent alerts")
+    );
+    let lines = entries.iter().map(|x| {
+        let fired_at = x.fired_at.with_timezone(&time_zone);
+
+        format!(
+            "{} — {}, {} {}$ ({})",
+            fired_at.format("%Y-%m-%d %H:%M"),
+            x.target.describe(language),
+            language.pick("ціна", "price"),
+            price::display(x.price),
+            x.source
+        )
+    });
+
+    for message in notifier::split_message(std::iter::once(header).chain(lines), "\n") {
+        bot.send_message(msg.chat.id, message)
+            .await
+            .context("Failed to send Telegram message")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_storage, Harness, CHAT_ID};
+
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn round_trips_backup() {
+        let storage = temp_storage();
+        let fired_at = Utc::now();
+
+        let mut history = History::new(HistoryConfig::default());
+        history.record(
+            ChatId(1),
+            Entry {
+                target: Target::Lower(dec!(6.2)),
+                price: dec!(6.1),
+                fired_at,
+                source: String::from("binance"),
+                label: Some(String::from("stop loss")),
+                expires_at: None,
+            },
+        );
+
+        history.backup(&storage).unwrap();
+        let restored = History::restore(&storage, HistoryConfig::default()).unwrap();
+
+        let entries = restored.recent(ChatId(1), 10);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].target, Target::Lower(dec!(6.2)));
+        assert_eq!(entries[0].price, dec!(6.1));
+        assert_eq!(entries[0].fired_at, fired_at);
+        assert_eq!(entries[0].source, "binance");
+        assert_eq!(entries[0].label.as_deref(), Some("stop loss"));
+    }
+
+    #[tokio::test]
+    async fn splits_long_history() {
+        let mut harness = Harness::new();
+        let max_entries = HistoryConfig::default().max_entries;
+
+        {
+            let mut history = harness.history.lock().await;
+            for minutes in 0..max_entries as i64 + 10 {
+                history.record(
+                    ChatId(CHAT_ID),
+                    Entry {
+                        target: Target::Higher(dec!(123456.789)),
+                        price: dec!(123456.789),
+                        fired_at: Utc::now() - Duration::minutes(1000 - minutes),
+                        source: String::from(price::SOURCE),
+                        label: None,
+                        expires_at: None,
+                    },
+                );
+            }
+        }
+
+        harness.send("/history 1000").await;
+
+        let messages = harness
+            .take_requests()
+            .await
+            .into_iter()
+            .filter_map(|x| x.text().map(String::from))
+            .collect::<Vec<_>>();
+
+        assert!(messages.len() > 1);
+        assert!(messages
+            .iter()
+            .all(|x| x.chars().count() <= notifier::MAX_MESSAGE_LENGTH));
+        assert!(messages[0].starts_with("Останні спрацювання (UTC):\n"));
+        assert_eq!(
+            messages.iter().map(|x| x.lines().count()).sum::<usize>(),
+            max_entries + 1
+        );
+    }
+}
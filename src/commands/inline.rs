@@ -0,0 +1,51 @@
+use crate::commands::{price, settings::Language, HandlerResult};
+
+use anyhow::Context;
+use log::{info, warn};
+
+use teloxide::{
+    prelude::*,
+    types::{
+        InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText,
+    },
+};
+
+/// Telegram may reuse our answer for the same query for this many seconds
+const CACHE_TIME: u32 = 5;
+
+pub async fn process(
+    bot: Bot,
+    q: InlineQuery,
+    quotes: price::Quotes,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving inline query...");
+
+    let results = match quotes.quote(price::QUOTE_MAX_AGE).await {
+        Ok(quote) => {
+            let text = price::format(&quote, language);
+
+            vec![InlineQueryResult::Article(
+                InlineQueryResultArticle::new(
+                    "price",
+                    format!("NEAR: {}$", price::display(quote.price)),
+                    InputMessageContent::Text(InputMessageContentText::new(text)),
+                )
+                .description(price::SOURCE),
+            )]
+        }
+        Err(err) => {
+            warn!("Failed to answer inline query: {err}");
+            Vec::new()
+        }
+    };
+
+    bot.answer_inline_query(q.id, results)
+        .cache_time(CACHE_TIME)
+        // The answer is in the language of the user who asked
+        .is_personal(true)
+        .await
+        .context("Failed to answer inline query")?;
+
+    Ok(())
+}
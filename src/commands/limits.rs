@@ -0,0 +1,302 @@
+//! Trigger quotas and command rate limiting, so a single chat can't slow down evaluation for everyone
+
+use crate::commands::{settings::Language, storage::Storage, store::TriggerStore, HandlerResult};
+use crate::config::{Config, LimitsConfig};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use teloxide::{prelude::*, types::UserId};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Format version of limits.bak
+const BACKUP_VERSION: u16 = 1;
+
+/// Why a trigger can't be added
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exceeded {
+    Chat(usize),
+    Global(usize),
+}
+
+impl Exceeded {
+    pub fn describe(&self, language: Language) -> String {
+        match (self, language) {
+            (Self::Chat(limit), Language::Ukrainian) => {
+                format!("досягнуто ліміт тригерів чату ({limit})")
+            }
+            (Self::Chat(limit), Language::English) => {
+                format!("the chat's trigger limit is reached ({limit})")
+            }
+            (Self::Global(limit), Language::Ukrainian) => {
+                format!("досягнуто загальний ліміт тригерів бота ({limit})")
+            }
+            (Self::Global(limit), Language::English) => {
+                format!("the bot's overall trigger limit is reached ({limit})")
+            }
+        }
+    }
+}
+
+/// A user sent more commands than allowed in the current window
+#[derive(Clone, Copy)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+    /// Only the first rejected command of a window is answered
+    pub first: bool,
+}
+
+struct Window {
+    started: Instant,
+    commands: u32,
+    rejected: bool,
+}
+
+#[derive(Default)]
+struct State {
+    /// Per-chat trigger quotas set by admins instead of the configured default
+    quotas: HashMap<ChatId, usize>,
+    windows: HashMap<UserId, Window>,
+    /// Expired windows are dropped at most once per window length
+    swept_at: Option<Instant>,
+}
+
+#[derive(Clone)]
+pub struct Limits {
+    config: LimitsConfig,
+    state: Arc<Mutex<State>>,
+    storage: Storage,
+}
+
+impl Limits {
+    pub fn new(config: LimitsConfig, quotas: HashMap<ChatId, usize>, storage: Storage) -> Self {
+        Self {
+            config,
+            state: Arc::new(Mutex::new(State {
+                quotas,
+                windows: HashMap::new(),
+                swept_at: None,
+            })),
+            storage,
+        }
+    }
+
+    pub const fn config(&self) -> &LimitsConfig {
+        &self.config
+    }
+
+    pub async fn quota(&self, chat_id: ChatId) -> usize {
+        self.state
+            .lock()
+            .await
+            .quotas
+            .get(&chat_id)
+            .copied()
+            .unwrap_or(self.config.max_triggers_per_chat)
+    }
+
+    /// Overrides the trigger quota of a chat, `None` restores the default
+    pub async fn set_quota(&self, chat_id: ChatId, quota: Option<usize>) -> Result<()> {
+        let mut state = self.state.lock().await;
+
+        match quota {
+            Some(quota) => state.quotas.insert(chat_id, quota),
+            None => state.quotas.remove(&chat_id),
+        };
+
+        backup(&self.storage, &state.quotas)
+    }
+
+    /// Checks whether one more trigger may be added to the chat
+    pub async fn check(
+        &self,
+        triggers: &TriggerStore<ChatId>,
+        chat_id: ChatId,
+    ) -> Result<(), Exceeded> {
+        let quota = self.quota(chat_id).await;
+
+        if triggers.get(&chat_id).map_or(0, Vec::len) >= quota {
+            return Err(Exceeded::Chat(quota));
+        }
+
+        if triggers.trigger_count() >= self.config.max_triggers {
+            return Err(Exceeded::Global(self.config.max_triggers));
+        }
+
+        Ok(())
+    }
+
+    /// Counts a command of the user, returns `Some` if it exceeds the rate limit
+    pub async fn record_command(&self, user_id: UserId, now: Instant) -> Option<RateLimited> {
+        let mut state = self.state.lock().await;
+
+        if state
+            .swept_at
+            .is_none_or(|x| now.duration_since(x) >= RATE_WINDOW)
+        {
+            state
+                .windows
+                .retain(|_, window| now.duration_since(window.started) < RATE_WINDOW);
+            state.swept_at = Some(now);
+        }
+
+        let window = state.windows.entry(user_id).or_insert(Window {
+            started: now,
+            commands: 0,
+            rejected: false,
+        });
+
+        if now.duration_since(window.started) >= RATE_WINDOW {
+            *window = Window {
+                started: now,
+                commands: 0,
+                rejected: false,
+            };
+        }
+
+        if window.commands < self.config.commands_per_minute {
+            window.commands += 1;
+            return None;
+        }
+
+        let first = !window.rejected;
+        window.rejected = true;
+
+        Some(RateLimited {
+            retry_after: RATE_WINDOW.saturating_sub(now.duration_since(window.started)),
+            first,
+        })
+    }
+}
+
+/// Dispatcher filter which lets commands of rate limited users through to `reject`, admins are never limited
+pub async fn check_rate(msg: Message, limits: Limits, config: Config) -> Option<RateLimited> {
+    let user = msg.from()?;
+
+    if config.admin_ids.contains(&user.id) {
+        return None;
+    }
+
+    let limited = limits.record_command(user.id, Instant::now()).await;
+
+    if limited.is_some() {
+        warn!("User {} exceeded the command rate limit", user.id);
+    }
+
+    limited
+}
+
+pub async fn reject(
+    bot: Bot,
+    msg: Message,
+    limited: RateLimited,
+    language: Language,
+) -> HandlerResult {
+    if !limited.first {
+        return Ok(());
+    }
+
+    let retry_after = limited.retry_after.as_secs().max(1);
+
+    bot.send_message(
+        msg.chat.id,
+        match language {
+            Language::Ukrainian => format!("Забагато команд, спробуйте через {retry_after} с"),
+            Language::English => format!("Too many commands, try again in {retry_after} s"),
+        },
+    )
+    .await
+    .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+fn backup(storage: &Storage, quotas: &HashMap<ChatId, usize>) -> Result<()> {
+    info!("Backing up trigger quotas...");
+
+    storage.save("limits.bak", BACKUP_VERSION, quotas)
+}
+
+pub fn restore(storage: &Storage) -> Result<HashMap<ChatId, usize>> {
+    info!("Restoring trigger quotas...");
+
+    storage.load("limits.bak", BACKUP_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::triggers::{Target, Trigger};
+    use crate::testing::temp_storage;
+
+    use rust_decimal::Decimal;
+
+    fn limits() -> Limits {
+        Limits::new(
+            LimitsConfig {
+                max_triggers_per_chat: 2,
+                max_triggers: 3,
+                commands_per_minute: 2,
+            },
+            HashMap::from([(ChatId(3), 5)]),
+            temp_storage(),
+        )
+    }
+
+    fn triggers(chats: &[(i64, usize)]) -> TriggerStore<ChatId> {
+        chats
+            .iter()
+            .map(|(chat_id, n)| {
+                let triggers = (0..*n)
+                    .map(|x| Trigger::new(Target::Higher(Decimal::from(x + 1))))
+                    .collect();
+
+                (ChatId(*chat_id), triggers)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn enforces_trigger_quotas() {
+        let limits = limits();
+
+        assert_eq!(limits.check(&triggers(&[(1, 1)]), ChatId(1)).await, Ok(()));
+        assert_eq!(
+            limits.check(&triggers(&[(1, 2)]), ChatId(1)).await,
+            Err(Exceeded::Chat(2))
+        );
+        assert_eq!(
+            limits.check(&triggers(&[(1, 2), (2, 1)]), ChatId(2)).await,
+            Err(Exceeded::Global(3))
+        );
+        assert_eq!(limits.check(&triggers(&[(3, 2)]), ChatId(3)).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn limits_command_rate() {
+        let limits = limits();
+        let (user_id, now) = (UserId(1), Instant::now());
+
+        assert!(limits.record_command(user_id, now).await.is_none());
+        assert!(limits.record_command(user_id, now).await.is_none());
+
+        let limited = limits.record_command(user_id, now).await.unwrap();
+        assert!(limited.first);
+        assert_eq!(limited.retry_after, RATE_WINDOW);
+        assert!(!limits.record_command(user_id, now).await.unwrap().first);
+
+        assert!(limits.record_command(UserId(2), now).await.is_none());
+        assert!(limits
+            .record_command(user_id, now + RATE_WINDOW)
+            .await
+            .is_none());
+
+        // Windows of users who went quiet are dropped
+        assert_eq!(limits.state.lock().await.windows.len(), 1);
+    }
+}
@@ -0,0 +1,396 @@
+//! Command menu shown by Telegram clients, generated from the command enums on every start
+//! so it never drifts from the commands the bot actually handles
+
+use crate::commands::{
+    settings::{Language, Settings},
+    storage::Storage,
+    AdminCommand, Command,
+};
+use crate::config::Config;
+
+use log::{error, info, warn};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+use teloxide::{
+    prelude::*,
+    types::{BotCommand, BotCommandScope, Chat, Recipient, UserId},
+    utils::command::BotCommands,
+};
+
+/// Languages with translated descriptions, other clients get the English ones from the enums
+const LANGUAGES: [&str; 1] = ["uk"];
+
+/// Format version of menu.bak
+const BACKUP_VERSION: u16 = 1;
+
+/// Commands about personal positions and files, which are hidden from the menu of group chats
+const PRIVATE_ONLY: [&str; 4] = ["position", "portfolio", "export", "import"];
+
+fn describe_uk(command: &str) -> Option<&'static str> {
+    let description = match command {
+        "start" => "почати роботу з ботом",
+        "help" => "показати список команд",
+        "getprice" => "поточна ціна NEAR",
+        "addtrigger" => "додати тригер, напр. /addtrigger > 7.5 24h фіксація прибутку",
+        "deletetrigger" => "видалити обраний тригер",
+        "deleteall" => "видалити всі тригери",
+        "listtriggers" => "список моїх тригерів",
+        "history" => "останні сповіщення, напр. /history 20",
+        "position" => "додати позицію, напр. /position add 100 @ 6.20",
+        "portfolio" => "вартість портфеля та P&L",
+        "settings" => "налаштування сповіщень",
+        "timezone" => "змінити часовий пояс, напр. /timezone Europe/Kyiv",
+        "export" => "експортувати тригери та налаштування у файл",
+        "import" => "імпортувати тригери та налаштування з файлу",
+        "adminhelp" => "список команд адміністратора",
+        "stats" => "статистика бота",
+        "broadcast" => "надіслати повідомлення всім чатам з тригерами",
+        "dumptriggers" => "вивантажити сховище тригерів",
+        "feedstatus" => "стан джерела ціни",
+        "setquota" => "змінити квоту тригерів чату",
+        _ => return None,
+    };
+
+    Some(description)
+}
+
+fn describe(command: &str, language: &str) -> Option<&'static str> {
+    match language {
+        "uk" => describe_uk(command),
+        _ => None,
+    }
+}
+
+/// Menu language with the descriptions of `language`, the enums are in English
+const fn code(language: Language) -> Option<&'static str> {
+    match language {
+        Language::Ukrainian => Some("uk"),
+        Language::English => None,
+    }
+}
+
+/// Commands of the enum without the `/` prefix, as the Bot API expects them
+fn commands<C: BotCommands>() -> Vec<BotCommand> {
+    C::bot_commands()
+        .into_iter()
+        .map(|x| BotCommand::new(x.command.trim_start_matches('/'), x.description))
+        .collect()
+}
+
+/// Commands for the given scope, with descriptions in `language` if it's set
+fn menu(group: bool, admin: bool, language: Option<&str>) -> Vec<BotCommand> {
+    let mut menu = commands::<Command>();
+
+    if group {
+        menu.retain(|x| !PRIVATE_ONLY.contains(&x.command.as_str()));
+    }
+
+    if admin {
+        menu.extend(commands::<AdminCommand>());
+    }
+
+    if let Some(language) = language {
+        for command in &mut menu {
+            match describe(&command.command, language) {
+                Some(description) => command.description = String::from(description),
+                None => warn!("No {language} description of /{}", command.command),
+            }
+        }
+    }
+
+    menu
+}
+
+/// Admins whose menus were registered, kept so that the menus of dropped admins can be deleted
+#[derive(Default, PartialEq, Serialize, Deserialize)]
+struct Admins {
+    admin_ids: Vec<UserId>,
+    admin_chat_id: Option<ChatId>,
+}
+
+impl Admins {
+    /// Scopes with admin commands, the flag is set for the scopes in a group
+    fn scopes(&self) -> Vec<(BotCommandScope, bool)> {
+        let mut scopes = Vec::new();
+
+        for user_id in &self.admin_ids {
+            scopes.push((
+                BotCommandScope::Chat {
+                    chat_id: Recipient::Id(ChatId(user_id.0 as i64)),
+                },
+                false,
+            ));
+
+            if let Some(admin_chat_id) = self.admin_chat_id {
+                scopes.push((
+                    BotCommandScope::ChatMember {
+                        chat_id: Recipient::Id(admin_chat_id),
+                        user_id: *user_id,
+                    },
+                    true,
+                ));
+            }
+        }
+
+        scopes
+    }
+}
+
+/// Menu languages of a scope, `None` for clients without a translation
+fn languages() -> impl Iterator<Item = Option<&'static str>> {
+    std::iter::once(None).chain(LANGUAGES.into_iter().map(Some))
+}
+
+/// Reply to /help, the commands of the chat menu in `language`
+pub fn help(language: Language) -> String {
+    match language {
+        Language::Ukrainian => {
+            let mut help = String::from("Підтримуються такі команди:\n");
+
+            for command in menu(false, false, code(language)) {
+                help.push_str(&format!("\n/{} — {}", command.command, command.description));
+            }
+
+            help
+        }
+        Language::English => Command::descriptions().to_string(),
+    }
+}
+
+/// Sets the menu of `scope` for every client language, with descriptions in `chosen` if the chat picked a language
+async fn set_menu(
+    bot: &Bot,
+    scope: &BotCommandScope,
+    group: bool,
+    admin: bool,
+    chosen: Option<Language>,
+) {
+    for language in languages() {
+        let descriptions = chosen.map_or(language, code);
+        let mut request = bot
+            .set_my_commands(menu(group, admin, descriptions))
+            .scope(scope.clone());
+
+        if let Some(language) = language {
+            request = request.language_code(language);
+        }
+
+        // An admin who never started the bot has no private chat yet, that shouldn't stop the rest
+        if let Err(err) = request.await {
+            warn!("Failed to register command menu for {scope:?} ({language:?}): {err}");
+        }
+    }
+}
+
+/// Replaces the menu of a chat which chose `language`, whatever the language of the client
+pub async fn register_chat(bot: &Bot, chat: &Chat, config: &Config, language: Language) {
+    info!("Registering command menu of chat {}...", chat.id);
+
+    let admin = chat.is_private() && config.admin_ids.iter().any(|x| x.0 as i64 == chat.id.0);
+    let scope = BotCommandScope::Chat {
+        chat_id: Recipient::Id(chat.id),
+    };
+
+    set_menu(bot, &scope, !chat.is_private(), admin, Some(language)).await;
+}
+
+/// Replaces the command menu of every scope, admins get admin commands in their private chats and in the admin chat,
+/// in the language of their chat's `settings`. Menus of admins dropped from the config since the previous start
+/// are deleted, or replaced by the user menu in the chat's language if the chat has settings
+pub async fn register(
+    bot: &Bot,
+    config: &Config,
+    storage: &Storage,
+    settings: &HashMap<ChatId, Settings>,
+) {
+    info!("Registering command menu...");
+
+    let admins = Admins {
+        admin_ids: config.admin_ids.clone(),
+        admin_chat_id: config.admin_chat_id,
+    };
+    // Nothing was registered before the first start
+    let registered = storage
+        .load::<Admins>("menu.bak", BACKUP_VERSION)
+        .unwrap_or_default();
+
+    let admin_scopes = admins.scopes();
+    let stale_scopes = registered
+        .scopes()
+        .into_iter()
+        .filter(|x| !admin_scopes.contains(x));
+
+    // Only the private chat of an admin has settings, menus of the admin chat apply to the admin alone
+    let chosen = |scope: &BotCommandScope| match scope {
+        BotCommandScope::Chat {
+            chat_id: Recipient::Id(chat_id),
+        } => settings.get(chat_id).map(|x| x.language),
+        _ => None,
+    };
+
+    for (scope, group) in stale_scopes {
+        if let Some(language) = chosen(&scope) {
+            set_menu(bot, &scope, group, false, Some(language)).await;
+            continue;
+        }
+
+        for language in languages() {
+            let mut request = bot.delete_my_commands().scope(scope.clone());
+
+            if let Some(language) = language {
+                request = request.language_code(language);
+            }
+
+            if let Err(err) = request.await {
+                warn!("Failed to delete command menu for {scope:?} ({language:?}): {err}");
+            }
+        }
+    }
+
+    let scopes = [
+        (BotCommandScope::AllPrivateChats, false, false),
+        (BotCommandScope::AllGroupChats, true, false),
+    ]
+    .into_iter()
+    .chain(
+        admin_scopes
+            .into_iter()
+            .map(|(scope, group)| (scope, group, true)),
+    );
+
+    for (scope, group, admin) in scopes {
+        set_menu(bot, &scope, group, admin, chosen(&scope)).await;
+    }
+
+    if registered != admins {
+        if let Err(err) = storage.save("menu.bak", BACKUP_VERSION, &admins) {
+            error!("Failed to backup registered admins, due to: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Harness;
+
+    use teloxide::types::UserId;
+
+    #[test]
+    fn describes_every_command() {
+        let all = commands::<Command>()
+            .into_iter()
+            .chain(commands::<AdminCommand>());
+
+        for command in all {
+            for language in LANGUAGES {
+                assert!(
+                    describe(&command.command, language).is_some(),
+                    "/{} has no {language} description",
+                    command.command
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn registers_menu_per_scope_and_language() {
+        let harness = Harness::new();
+        let config = Config {
+            admin_ids: vec![UserId(7)],
+            ..Config::default()
+        };
+
+        register(&harness.bot, &config, &harness.storage, &HashMap::new()).await;
+
+        let requests = harness.take_requests().await;
+        assert_eq!(requests.len(), 3 * (LANGUAGES.len() + 1));
+        assert!(requests
+            .iter()
+            .all(|x| x.method.eq_ignore_ascii_case("setmycommands")));
+
+        let names = |scope: &str, language: Option<&str>| {
+            let request = requests
+                .iter()
+                .find(|x| {
+                    x.body["scope"]["type"] == scope && x.body["language_code"].as_str() == language
+                })
+                .unwrap();
+
+            request.body["commands"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x["command"].as_str().unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let private = names("all_private_chats", None);
+        assert!(private.contains(&String::from("addtrigger")));
+        assert!(private.contains(&String::from("export")));
+        assert!(!private.contains(&String::from("stats")));
+
+        let group = names("all_group_chats", Some("uk"));
+        assert!(group.contains(&String::from("addtrigger")));
+        assert!(!group.contains(&String::from("export")));
+
+        let admin = names("chat", None);
+        assert!(admin.contains(&String::from("stats")));
+        assert!(admin.contains(&String::from("setquota")));
+    }
+
+    #[tokio::test]
+    async fn deletes_menus_of_dropped_admins() {
+        let harness = Harness::new();
+        let config = Config {
+            admin_ids: vec![UserId(7), UserId(8)],
+            admin_chat_id: Some(ChatId(-100)),
+            ..Config::default()
+        };
+
+        register(&harness.bot, &config, &harness.storage, &HashMap::new()).await;
+        assert!(harness
+            .take_requests()
+            .await
+            .iter()
+            .all(|x| x.method.eq_ignore_ascii_case("setmycommands")));
+
+        let config = Config {
+            admin_ids: vec![UserId(8)],
+            ..config
+        };
+        register(&harness.bot, &config, &harness.storage, &HashMap::new()).await;
+
+        let deleted = harness
+            .take_requests()
+            .await
+            .into_iter()
+            .filter(|x| x.method.eq_ignore_ascii_case("deletemycommands"))
+            .map(|x| {
+                (
+                    x.body["scope"]["type"].as_str().unwrap().to_owned(),
+                    x.body["scope"]["chat_id"].as_i64().unwrap(),
+                    x.body["scope"]["user_id"].as_u64(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let expected = [
+            (String::from("chat"), 7, None),
+            (String::from("chat_member"), -100, Some(7)),
+        ];
+        assert_eq!(deleted.len(), expected.len() * (LANGUAGES.len() + 1));
+        assert!(expected.iter().all(|x| deleted.contains(x)));
+
+        // Nothing is left to delete on the next start
+        register(&harness.bot, &config, &harness.storage, &HashMap::new()).await;
+        assert!(harness
+            .take_requests()
+            .await
+            .iter()
+            .all(|x| x.method.eq_ignore_ascii_case("setmycommands")));
+    }
+}
@@ -1,8 +1,28 @@
 pub mod schema;
 
+pub mod admin;
+pub mod alerts;
+pub mod callback;
+pub mod engine;
+pub mod export;
+pub mod feed;
 pub mod help;
+pub mod history;
+pub mod inline;
+pub mod limits;
+pub mod menu;
+pub mod notifier;
+pub mod portfolio;
 pub mod price;
+pub mod settings;
+pub mod storage;
+pub mod store;
 pub mod triggers;
+pub mod welcome;
+
+use callback::Callback;
+use settings::Language;
+use store::TriggerStore;
 
 use anyhow::Context;
 use log::warn;
@@ -16,15 +36,57 @@ use teloxide::{dispatching::dialogue::InMemStorage, prelude::*};
 type MyDialogue = Dialogue<State, InMemStorage<State>>;
 type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+/// State needed to add triggers, built from the dispatcher dependencies so handlers don't pass each piece along
+#[derive(Clone)]
+pub struct Deps {
+    pub triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    pub settings: Arc<Mutex<HashMap<ChatId, settings::Settings>>>,
+    pub portfolios: Arc<Mutex<HashMap<ChatId, portfolio::Portfolio>>>,
+    pub limits: limits::Limits,
+    pub quotes: price::Quotes,
+    pub storage: storage::Storage,
+}
+
+impl Deps {
+    pub fn new(
+        triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+        settings: Arc<Mutex<HashMap<ChatId, settings::Settings>>>,
+        portfolios: Arc<Mutex<HashMap<ChatId, portfolio::Portfolio>>>,
+        limits: limits::Limits,
+        quotes: price::Quotes,
+        storage: storage::Storage,
+    ) -> Self {
+        Self {
+            triggers,
+            settings,
+            portfolios,
+            limits,
+            quotes,
+            storage,
+        }
+    }
+}
+
 #[derive(Clone, Default)]
 pub enum State {
     #[default]
     Start,
     ReceiveTriggerType,
     ReceivePrice {
+        target: triggers::Target,
+    },
+    ReceiveExpiry {
+        target: triggers::Target,
+    },
+    ReceiveLabel {
+        trigger: triggers::Trigger,
+    },
+    /// The trigger in the opposite direction, proposed because the threshold was already reached
+    ConfirmConvert {
         trigger: triggers::Trigger,
     },
     DeleteTrigger,
+    ReceiveImport,
 }
 
 #[derive(BotCommands, Clone)]
@@ -33,39 +95,99 @@ pub enum State {
     description = "These commands are supported:"
 )]
 pub enum Command {
+    #[command(description = "start using the bot")]
+    Start,
     #[command(description = "display this text")]
     Help,
 
     #[command(description = "get current NEAR price")]
     GetPrice,
 
-    #[command(description = "add new trigger")]
-    AddTrigger,
+    #[command(
+        description = "add new trigger, e.g. /addtrigger > 7.5 24h take profit or /addtrigger pnl > 20%"
+    )]
+    AddTrigger(String),
     #[command(description = "delete selected trigger")]
     DeleteTrigger,
     #[command(description = "delete all triggers")]
     DeleteAll,
     #[command(description = "list all my triggers")]
     ListTriggers,
+    #[command(description = "show recently fired alerts, e.g. /history 20")]
+    History(String),
+
+    #[command(description = "add a paper-trading position, e.g. /position add 100 @ 6.20")]
+    Position(String),
+    #[command(description = "show portfolio value and P&L")]
+    Portfolio,
+
+    #[command(description = "notification settings")]
+    Settings,
+    #[command(description = "set my time zone, e.g. /timezone Europe/Kyiv")]
+    TimeZone(String),
+
+    #[command(description = "export my triggers and settings to a file")]
+    Export,
+    #[command(description = "import triggers and settings from a file")]
+    Import,
+}
+
+#[derive(BotCommands, Clone)]
+#[command(
+    rename_rule = "lowercase",
+    description = "These admin commands are supported:"
+)]
+pub enum AdminCommand {
+    #[command(description = "display this text")]
+    AdminHelp,
+    #[command(description = "show bot statistics")]
+    Stats,
+    #[command(description = "send a message to all chats with triggers")]
+    Broadcast(String),
+    #[command(description = "export triggers storage")]
+    DumpTriggers,
+    #[command(description = "show price feed status")]
+    FeedStatus,
+    #[command(description = "set trigger quota of a chat: /setquota <chat id> <n | default>")]
+    SetQuota(String),
 }
 
 pub async fn start(
     bot: Bot,
     dialogue: MyDialogue,
     q: CallbackQuery,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<triggers::Trigger>>>>,
+    deps: Deps,
+    history: Arc<Mutex<history::History>>,
+    language: Language,
 ) -> HandlerResult {
-    if let Some(data) = q.data.clone() {
-        if data == "Lower" || data == "Higher" {
-            triggers::receive_trigger_type(bot, dialogue, q).await?;
-        } else if data.parse::<price::Price>().is_ok() {
-            triggers::choose_trigger_to_delete(bot, dialogue, q, triggers).await?;
-        } else {
-            warn!("Unknown callback query data: {}", data);
-
-            bot.send_message(dialogue.chat_id(), "Невідома команда")
-                .await
-                .context("Failed to send Telegram message")?;
+    match Callback::from_query(&q) {
+        Some(Callback::TriggerType(_)) => {
+            triggers::receive_trigger_type(bot, dialogue, q, language).await?;
+        }
+        Some(Callback::DeleteTrigger(_)) => {
+            triggers::choose_trigger_to_delete(bot, dialogue, q, deps, language).await?;
+        }
+        Some(Callback::Alert(..)) => {
+            alerts::process(bot, dialogue.clone(), q, deps, history, language).await?
+        }
+        Some(
+            Callback::NoExpiry
+            | Callback::NoLabel
+            | Callback::ConfirmConvert
+            | Callback::CancelConvert
+            | Callback::Settings(_)
+            | Callback::QuickTrigger(_)
+            | Callback::Language(_),
+        )
+        | None => {
+            warn!("Unknown callback query data: {:?}", q.data);
+
+            bot.send_message(
+                dialogue.chat_id(),
+                language.pick("Невідома команда", "Unknown command"),
+            )
+            .await
+            .context("Failed to send Telegram message")?;
         }
     }
 
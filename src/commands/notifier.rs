@@ -0,0 +1,325 @@
+use crate::metrics::{Metrics, METRICS};
+
+use log::warn;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Duration, Instant};
+
+use teloxide::{prelude::*, types::InlineKeyboardMarkup, ApiError, RequestError};
+
+/// Telegram allows about 30 messages per second across all chats
+const GLOBAL_INTERVAL: Duration = Duration::from_millis(1000 / 30);
+/// ... no more than one message per second to a private chat
+const PRIVATE_CHAT_INTERVAL: Duration = Duration::from_secs(1);
+/// ... and no more than 20 messages per minute to a group
+const GROUP_CHAT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Telegram refuses longer messages
+pub const MAX_MESSAGE_LENGTH: usize = 4096;
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+pub enum Delivery {
+    Sent,
+    /// The bot was blocked, kicked or the chat no longer exists
+    ChatUnavailable,
+}
+
+/// Joins `lines` with `separator` into as few messages as fit into [`MAX_MESSAGE_LENGTH`],
+/// truncating a line which doesn't fit into a message on its own
+pub fn split_message(lines: impl IntoIterator<Item = String>, separator: &str) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut message = String::new();
+    let mut length = 0;
+
+    for line in lines {
+        let line = line.chars().take(MAX_MESSAGE_LENGTH).collect::<String>();
+        let line_length = line.chars().count();
+
+        if length > 0 && length + separator.chars().count() + line_length > MAX_MESSAGE_LENGTH {
+            messages.push(std::mem::take(&mut message));
+            length = 0;
+        }
+
+        if length > 0 {
+            message.push_str(separator);
+            length += separator.chars().count();
+        }
+
+        message.push_str(&line);
+        length += line_length;
+    }
+
+    if length > 0 {
+        messages.push(message);
+    }
+
+    messages
+}
+
+#[derive(Default)]
+struct Limits {
+    next_global: Option<Instant>,
+    next_chat: HashMap<ChatId, Instant>,
+}
+
+/// Sends outbound messages within Telegram's rate limits, retrying on flood control and network errors
+#[derive(Clone)]
+pub struct Notifier {
+    bot: Bot,
+    limits: Arc<Mutex<Limits>>,
+}
+
+impl Notifier {
+    pub fn new(bot: Bot) -> Self {
+        Self {
+            bot,
+            limits: Arc::default(),
+        }
+    }
+
+    async fn wait_for_slot(&self, chat_id: ChatId) {
+        let slot = {
+            let mut limits = self.limits.lock().await;
+            let now = Instant::now();
+
+            let slot = [
+                Some(now),
+                limits.next_global,
+                limits.next_chat.get(&chat_id).copied(),
+            ]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or(now);
+
+            let chat_interval = if chat_id.is_user() {
+                PRIVATE_CHAT_INTERVAL
+            } else {
+                GROUP_CHAT_INTERVAL
+            };
+
+            limits.next_global = Some(slot + GLOBAL_INTERVAL);
+            limits.next_chat.insert(chat_id, slot + chat_interval);
+            limits.next_chat.retain(|_, next| *next > now);
+
+            slot
+        };
+
+        sleep_until(slot).await;
+    }
+
+    pub async fn send(
+        &self,
+        chat_id: ChatId,
+        text: &str,
+        reply_markup: Option<&InlineKeyboardMarkup>,
+        silent: bool,
+    ) -> Result<Delivery, RequestError> {
+        let result = self.try_send(chat_id, text, reply_markup, silent).await;
+
+        match result {
+            Ok(Delivery::Sent) => Metrics::inc(&METRICS.messages_sent),
+            Ok(Delivery::ChatUnavailable) | Err(_) => Metrics::inc(&METRICS.messages_failed),
+        }
+
+        result
+    }
+
+    async fn try_send(
+        &self,
+        chat_id: ChatId,
+        text: &str,
+        reply_markup: Option<&InlineKeyboardMarkup>,
+        silent: bool,
+    ) -> Result<Delivery, RequestError> {
+        let mut backoff = INITIAL_BACKOFF;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            self.wait_for_slot(chat_id).await;
+
+            let mut request = self
+                .bot
+                .send_message(chat_id, text)
+                .disable_notification(silent);
+
+            if let Some(reply_markup) = reply_markup {
+                request = request.reply_markup(reply_markup.clone());
+            }
+
+            match request.await {
+                Ok(_) => return Ok(Delivery::Sent),
+                Err(RequestError::Api(
+                    ApiError::BotBlocked
+                    | ApiError::ChatNotFound
+                    | ApiError::UserDeactivated
+                    | ApiError::GroupDeactivated
+                    | ApiError::BotKicked
+                    | ApiError::BotKickedFromSupergroup
+                    | ApiError::CantInitiateConversation,
+                )) => return Ok(Delivery::ChatUnavailable),
+                Err(RequestError::RetryAfter(retry_after)) if attempt < MAX_ATTEMPTS => {
+                    warn!("Flood control for chat {chat_id}, retrying after {retry_after:?}");
+
+                    let mut limits = self.limits.lock().await;
+                    limits.next_global = Some(Instant::now() + retry_after);
+                }
+                Err(RequestError::Network(err)) if attempt < MAX_ATTEMPTS => {
+                    warn!(
+                        "Failed to send message to chat {chat_id}, retrying in {backoff:?}: {err}"
+                    );
+
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        unreachable!("the last attempt always returns")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Harness, CHAT_ID};
+
+    use serde_json::json;
+    use std::net::{SocketAddr, TcpListener};
+
+    fn offline_notifier() -> Notifier {
+        // Nothing listens on a port once its listener is dropped
+        let addr = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        Notifier::new(
+            Bot::new("TEST:TOKEN").set_api_url(format!("http://{addr}/").parse().unwrap()),
+        )
+    }
+
+    fn sent_messages(requests: &[crate::testing::Request]) -> usize {
+        requests
+            .iter()
+            .filter(|x| x.method.eq_ignore_ascii_case("sendmessage"))
+            .count()
+    }
+
+    #[test]
+    fn splits_long_messages() {
+        let line = "ї".repeat(1000);
+
+        let messages = split_message(vec![line.clone(); 5], "\n");
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], [line.as_str(); 4].join("\n"));
+        assert_eq!(messages[1], line);
+
+        let messages = split_message([String::from("a"), "b".repeat(5000)], "\n\n");
+        assert_eq!(messages, ["a", &"b".repeat(MAX_MESSAGE_LENGTH)]);
+
+        assert!(split_message(Vec::new(), "\n").is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spaces_messages_within_rate_limits() {
+        let notifier = offline_notifier();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            notifier.wait_for_slot(ChatId(1)).await;
+        }
+        assert_eq!(start.elapsed(), PRIVATE_CHAT_INTERVAL * 2);
+
+        let notifier = offline_notifier();
+        let start = Instant::now();
+        notifier.wait_for_slot(ChatId(-1)).await;
+        notifier.wait_for_slot(ChatId(-1)).await;
+        assert_eq!(start.elapsed(), GROUP_CHAT_INTERVAL);
+
+        // Different chats only wait for the global limit
+        let notifier = offline_notifier();
+        let start = Instant::now();
+
+        for chat_id in 1..=31 {
+            notifier.wait_for_slot(ChatId(chat_id)).await;
+        }
+        assert_eq!(start.elapsed(), GLOBAL_INTERVAL * 30);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backs_off_on_network_errors() {
+        let start = Instant::now();
+
+        let result = offline_notifier()
+            .send(ChatId(1), "alert", None, false)
+            .await;
+
+        assert!(matches!(result, Err(RequestError::Network(_))));
+        // 0.5 + 1 + 2 + 4 seconds between the attempts
+        assert!(start.elapsed() >= INITIAL_BACKOFF * 15);
+    }
+
+    #[tokio::test]
+    async fn retries_after_flood_control() {
+        let harness = Harness::new();
+        harness
+            .fail_next(json!({
+                "ok": false,
+                "error_code": 429,
+                "description": "Too Many Requests: retry after 1",
+                "parameters": { "retry_after": 1 },
+            }))
+            .await;
+
+        let start = Instant::now();
+        let result = harness
+            .notifier
+            .send(ChatId(CHAT_ID), "alert", None, false)
+            .await;
+
+        assert!(matches!(result, Ok(Delivery::Sent)));
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert_eq!(sent_messages(&harness.take_requests().await), 2);
+    }
+
+    #[tokio::test]
+    async fn reports_unavailable_chats_without_retrying() {
+        let harness = Harness::new();
+        harness
+            .fail_next(json!({
+                "ok": false,
+                "error_code": 403,
+                "description": "Forbidden: bot was blocked by the user",
+            }))
+            .await;
+
+        let result = harness
+            .notifier
+            .send(ChatId(CHAT_ID), "alert", None, false)
+            .await;
+
+        assert!(matches!(result, Ok(Delivery::ChatUnavailable)));
+        assert_eq!(sent_messages(&harness.take_requests().await), 1);
+
+        harness
+            .fail_next(json!({
+                "ok": false,
+                "error_code": 400,
+                "description": "Bad Request: message text is empty",
+            }))
+            .await;
+
+        let result = harness
+            .notifier
+            .send(ChatId(CHAT_ID), "", None, false)
+            .await;
+
+        assert!(matches!(result, Err(RequestError::Api(_))));
+        assert_eq!(sent_messages(&harness.take_requests().await), 1);
+    }
+}
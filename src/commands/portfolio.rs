@@ -0,0 +1,354 @@
+use crate::commands::{price, settings::Language, storage::Storage, HandlerResult};
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use teloxide::prelude::*;
+
+fn usage(language: Language) -> &'static str {
+    language.pick(
+        "Формат:\n/position add 100 @ 6.20\n/position add 100 — за поточною ціною\n/position clear",
+        "Format:\n/position add 100 @ 6.20\n/position add 100 — at the current price\n/position clear",
+    )
+}
+
+/// Larger numbers could overflow the decimal arithmetic of portfolio values
+const MAX_NUMBER: Decimal = Decimal::from_parts(1_000_000_000, 0, 0, false, 0);
+/// Format version of portfolio.bak
+const BACKUP_VERSION: u16 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Position {
+    pub amount: Decimal,
+    pub entry_price: price::Price,
+    pub opened_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Portfolio {
+    pub positions: Vec<Position>,
+}
+
+impl Portfolio {
+    pub fn amount(&self) -> Decimal {
+        self.positions.iter().map(|x| x.amount).sum()
+    }
+
+    pub fn cost(&self) -> Decimal {
+        self.positions
+            .iter()
+            .map(|x| x.amount * x.entry_price)
+            .sum()
+    }
+
+    pub fn value(&self, price: price::Price) -> Decimal {
+        self.amount() * price
+    }
+
+    pub fn pnl(&self, price: price::Price) -> Decimal {
+        self.value(price) - self.cost()
+    }
+
+    /// Unrealized P&L relative to the cost, `None` for an empty portfolio
+    /// or a cost too small to divide by
+    pub fn pnl_percent(&self, price: price::Price) -> Option<Decimal> {
+        let cost = self.cost();
+
+        if cost <= Decimal::ZERO {
+            return None;
+        }
+
+        self.pnl(price)
+            .checked_div(cost)?
+            .checked_mul(Decimal::ONE_HUNDRED)
+    }
+}
+
+enum Action {
+    Add {
+        amount: Decimal,
+        entry_price: Option<price::Price>,
+    },
+    Clear,
+}
+
+fn parse_number(text: &str) -> Option<Decimal> {
+    price::parse(text.trim().trim_end_matches('$'))
+        .filter(|x| *x > Decimal::ZERO && *x <= MAX_NUMBER)
+}
+
+fn parse_arguments(args: &str) -> Option<Action> {
+    let args = args.trim();
+
+    if args == "clear" {
+        return Some(Action::Clear);
+    }
+
+    let args = args.strip_prefix("add")?;
+    let (amount, entry_price) = match args.split_once('@') {
+        Some((amount, entry_price)) => (
+            amount,
+            // Anything cheaper than a tick can't be a real fill
+            Some(parse_number(entry_price).filter(|x| *x >= price::TICK_SIZE)?),
+        ),
+        None => (args, None),
+    };
+
+    Some(Action::Add {
+        amount: parse_number(amount)?,
+        entry_price,
+    })
+}
+
+pub async fn position(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    portfolios: Arc<Mutex<HashMap<ChatId, Portfolio>>>,
+    quotes: price::Quotes,
+    storage: Storage,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving position command...");
+
+    let message = match parse_arguments(&args) {
+        Some(Action::Add {
+            amount,
+            entry_price,
+        }) => {
+            let entry_price = match entry_price {
+                Some(entry_price) => entry_price,
+                None => match quotes.quote(price::QUOTE_MAX_AGE).await {
+                    Ok(quote) => quote.price,
+                    Err(err) => {
+                        bot.send_message(msg.chat.id, price::fetch_failed(&err, language))
+                            .await
+                            .context("Failed to send Telegram message")?;
+
+                        return Ok(());
+                    }
+                },
+            };
+
+            let mut locked_portfolios = portfolios.lock().await;
+            locked_portfolios
+                .entry(msg.chat.id)
+                .or_default()
+                .positions
+                .push(Position {
+                    amount,
+                    entry_price,
+                    opened_at: Utc::now(),
+                });
+
+            if let Err(err) = backup(&storage, &locked_portfolios) {
+                error!("Failed to backup portfolios, due to: {}", err);
+            }
+
+            let entry_price = price::display(entry_price);
+
+            match language {
+                Language::Ukrainian => format!("Позицію додано: {amount} NEAR по {entry_price}$"),
+                Language::English => format!("Position added: {amount} NEAR at {entry_price}$"),
+            }
+        }
+        Some(Action::Clear) => {
+            let mut locked_portfolios = portfolios.lock().await;
+            locked_portfolios.remove(&msg.chat.id);
+
+            if let Err(err) = backup(&storage, &locked_portfolios) {
+                error!("Failed to backup portfolios, due to: {}", err);
+            }
+
+            String::from(language.pick("Усі позиції видалено", "All positions were cleared"))
+        }
+        None => {
+            warn!("User provided invalid position arguments: {args:?}");
+
+            String::from(usage(language))
+        }
+    };
+
+    bot.send_message(msg.chat.id, message)
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub async fn show(
+    bot: Bot,
+    msg: Message,
+    portfolios: Arc<Mutex<HashMap<ChatId, Portfolio>>>,
+    quotes: price::Quotes,
+    language: Language,
+) -> HandlerResult {
+    info!("Showing portfolio...");
+
+    let portfolio = portfolios
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .cloned()
+        .unwrap_or_default();
+
+    if portfolio.positions.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "{}\n\n{}",
+                language.pick("У вас немає позицій", "You have no positions"),
+                usage(language)
+            ),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    }
+
+    let price = match quotes.quote(price::QUOTE_MAX_AGE).await {
+        Ok(quote) => quote.price,
+        Err(err) => {
+            bot.send_message(msg.chat.id, price::fetch_failed(&err, language))
+                .await
+                .context("Failed to send Telegram message")?;
+
+            return Ok(());
+        }
+    };
+
+    let mut message = String::from(language.pick("Позиції:\n", "Positions:\n"));
+
+    for position in &portfolio.positions {
+        message.push_str(&format!(
+            "{} NEAR {} {}$ ({})\n",
+            position.amount,
+            language.pick("по", "at"),
+            price::display(position.entry_price),
+            position.opened_at.format("%Y-%m-%d")
+        ));
+    }
+
+    let (amount, value, current, cost, pnl, pnl_percent) = (
+        portfolio.amount(),
+        portfolio.value(price),
+        price::display(price),
+        portfolio.cost(),
+        portfolio.pnl(price),
+        portfolio.pnl_percent(price).unwrap_or_default(),
+    );
+
+    message.push_str(&match language {
+        Language::Ukrainian => format!(
+            "\nВсього: {amount} NEAR\n\
+             Вартість: {value:.2}$ (ціна {current}$)\n\
+             Вкладено: {cost:.2}$\n\
+             P&L: {pnl:+.2}$ ({pnl_percent:+.2}%)"
+        ),
+        Language::English => format!(
+            "\nTotal: {amount} NEAR\n\
+             Value: {value:.2}$ (price {current}$)\n\
+             Invested: {cost:.2}$\n\
+             P&L: {pnl:+.2}$ ({pnl_percent:+.2}%)"
+        ),
+    });
+
+    bot.send_message(msg.chat.id, message)
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub fn backup(storage: &Storage, portfolios: &HashMap<ChatId, Portfolio>) -> Result<()> {
+    info!("Backing up portfolios...");
+
+    storage.save("portfolio.bak", BACKUP_VERSION, portfolios)
+}
+
+pub fn restore(storage: &Storage) -> Result<HashMap<ChatId, Portfolio>> {
+    info!("Restoring portfolios...");
+
+    storage.load("portfolio.bak", BACKUP_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_storage;
+
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn rejects_entry_prices_below_tick() {
+        assert!(matches!(
+            parse_arguments("add 1 @ 0.001"),
+            Some(Action::Add {
+                entry_price: Some(_),
+                ..
+            })
+        ));
+        assert!(parse_arguments("add 1 @ 0.0009").is_none());
+        assert!(parse_arguments("add 1 @ 0.0000000000000000000000000001").is_none());
+    }
+
+    #[test]
+    fn survives_tiny_costs() {
+        // As a backup written before entry prices were checked could hold
+        let portfolio = Portfolio {
+            positions: vec![Position {
+                amount: dec!(1),
+                entry_price: dec!(0.0000000000000000000000000001),
+                opened_at: Utc::now(),
+            }],
+        };
+
+        assert_eq!(portfolio.pnl_percent(dec!(6)), None);
+        assert_eq!(portfolio.pnl_percent(dec!(1000)), None);
+        assert_eq!(
+            Portfolio {
+                positions: vec![Position {
+                    amount: dec!(100),
+                    entry_price: dec!(5),
+                    opened_at: Utc::now(),
+                }],
+            }
+            .pnl_percent(dec!(6)),
+            Some(dec!(20))
+        );
+    }
+
+    #[test]
+    fn round_trips_backup() {
+        let storage = temp_storage();
+        let opened_at = Utc::now();
+        let portfolios = HashMap::from([(
+            ChatId(1),
+            Portfolio {
+                positions: vec![Position {
+                    amount: dec!(100.5),
+                    entry_price: dec!(6.2),
+                    opened_at,
+                }],
+            },
+        )]);
+
+        backup(&storage, &portfolios).unwrap();
+        let restored = restore(&storage).unwrap();
+
+        let positions = &restored[&ChatId(1)].positions;
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].amount, dec!(100.5));
+        assert_eq!(positions[0].entry_price, dec!(6.2));
+        assert_eq!(positions[0].opened_at, opened_at);
+    }
+}
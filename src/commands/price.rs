@@ -1,52 +1,335 @@
-use crate::commands::HandlerResult;
+use crate::commands::{settings::Language, HandlerResult};
+use crate::metrics::{Metrics, METRICS};
 
 use anyhow::{anyhow, Context, Result};
-use log::{error, info};
+use log::{error, info, warn};
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 
 use teloxide::prelude::*;
 
-use binance::api::Binance;
 use binance::market::Market;
 
-pub type Price = f64;
+use rust_decimal::{Decimal, RoundingStrategy};
 
-pub async fn get() -> Result<Price> {
-    let result = tokio::task::spawn_blocking(move || {
-        let market: Market = Binance::new(None, None);
-        market.get_price("NEARUSDT")
-    })
-    .await
-    .context("Failed to spawn blocking task")?;
+pub type Price = Decimal;
 
-    match result {
-        Ok(symbol_price) => Ok(symbol_price.price),
-        Err(err) => {
-            error!("Failed to get NEAR price: {err}");
-            Err(anyhow!("Error while parsing NEAR price: {:?}", err))
+/// Price filter of NEARUSDT on the exchange, quotes are always a multiple of it
+pub const TICK_SIZE: Price = Decimal::from_parts(1, 0, 0, false, 3);
+
+/// Exchange and symbol the price is fetched from
+pub const SOURCE: &str = "Binance NEARUSDT";
+
+/// Bursts of user requests are answered from the same quote
+pub const QUOTE_MAX_AGE: Duration = Duration::from_secs(5);
+/// Quotes older than this must not be acted upon
+pub const STALE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy)]
+pub struct Quote {
+    pub price: Price,
+    pub fetched_at: Instant,
+}
+
+impl Quote {
+    pub fn age(&self) -> Duration {
+        self.fetched_at.elapsed()
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.age() > STALE_AFTER
+    }
+}
+
+/// Where prices come from, the exchange in production and a scripted price in tests
+pub trait Source: Send + Sync + 'static {
+    /// Fetches the current price, called on a blocking thread
+    fn fetch(&self) -> Result<Price>;
+}
+
+/// NEARUSDT ticker of Binance
+pub struct Binance;
+
+impl Source for Binance {
+    fn fetch(&self) -> Result<Price> {
+        let market: Market = binance::api::Binance::new(None, None);
+        let symbol_price = market
+            .get_price("NEARUSDT")
+            .map_err(|err| anyhow!("Error while parsing NEAR price: {:?}", err))?;
+
+        Decimal::try_from(symbol_price.price).context("Exchange returned a price out of range")
+    }
+}
+
+/// Source returning a price set by the test, failing while none is set
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct Scripted {
+    price: Arc<std::sync::Mutex<Option<Price>>>,
+    delay: Duration,
+    fetches: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+#[cfg(test)]
+impl Scripted {
+    pub fn set(&self, price: Option<Price>) {
+        *self.price.lock().unwrap() = price;
+    }
+
+    pub fn fetches(&self) -> usize {
+        self.fetches.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+impl Source for Scripted {
+    fn fetch(&self) -> Result<Price> {
+        std::thread::sleep(self.delay);
+        self.fetches
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+        (*self.price.lock().unwrap()).ok_or_else(|| anyhow!("Source is unavailable"))
+    }
+}
+
+/// The last fetched quote, shared by all consumers of the same source
+#[derive(Clone)]
+pub struct Quotes {
+    source: Arc<dyn Source>,
+    /// The lock is held while fetching, so concurrent callers wait for a single request to the source
+    last_quote: Arc<Mutex<Option<Quote>>>,
+}
+
+impl Quotes {
+    pub fn new(source: impl Source) -> Self {
+        Self {
+            source: Arc::new(source),
+            last_quote: Arc::default(),
+        }
+    }
+
+    /// Returns the cached quote if it's younger than `max_age`, otherwise fetches a new one.
+    /// If the source is unavailable, falls back to the cached quote, which may be stale
+    pub async fn quote(&self, max_age: Duration) -> Result<Quote> {
+        let mut last_quote = self.last_quote.lock().await;
+
+        if let Some(quote) = last_quote.filter(|x| x.age() <= max_age) {
+            return Ok(quote);
+        }
+
+        match self.fetch().await {
+            Ok(price) => {
+                let quote = Quote {
+                    price,
+                    fetched_at: Instant::now(),
+                };
+                *last_quote = Some(quote);
+
+                Ok(quote)
+            }
+            Err(err) => match *last_quote {
+                Some(quote) => {
+                    warn!("Using cached NEAR price of age {:?}: {err}", quote.age());
+                    Ok(quote)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Replaces the cached quote, so tests don't depend on the source
+    #[cfg(test)]
+    pub async fn set(&self, price: Price, fetched_at: Instant) {
+        *self.last_quote.lock().await = Some(Quote { price, fetched_at });
+    }
+
+    async fn fetch(&self) -> Result<Price> {
+        Metrics::inc(&METRICS.price_fetches);
+        let started = Instant::now();
+
+        let source = self.source.clone();
+        let result = tokio::task::spawn_blocking(move || source.fetch())
+            .await
+            .context("Failed to spawn blocking task")?;
+
+        METRICS.price_fetch_latency.observe(started.elapsed());
+
+        match result {
+            Ok(price) => {
+                METRICS.record_price();
+                Ok(to_tick(price))
+            }
+            Err(err) => {
+                Metrics::inc(&METRICS.price_fetch_errors);
+
+                error!("Failed to get NEAR price: {err}");
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Rounds a price to the nearest multiple of the exchange tick size
+pub fn to_tick(price: Price) -> Price {
+    price.round_dp_with_strategy(TICK_SIZE.scale(), RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// Parses a number entered by a user, accepting a comma as the decimal separator
+pub fn parse(text: &str) -> Option<Decimal> {
+    Decimal::from_str(&text.trim().replace(',', ".")).ok()
+}
+
+/// Formats a price with at least two decimal places, keeping the digits finer than a cent
+pub fn display(price: Price) -> String {
+    let price = price.normalize();
+
+    if price.scale() < 2 {
+        format!("{price:.2}")
+    } else {
+        price.to_string()
+    }
+}
+
+pub fn format(quote: &Quote, language: Language) -> String {
+    let age = quote.age().as_secs();
+
+    let price = display(quote.price);
+
+    match (language, quote.is_stale()) {
+        (Language::Ukrainian, true) => {
+            format!("Поточна ціна NEAR: {price}$ (застаріла, оновлена {age} с тому).")
+        }
+        (Language::Ukrainian, false) => {
+            format!("Поточна ціна NEAR: {price}$ (оновлена {age} с тому).")
+        }
+        (Language::English, true) => {
+            format!("Current NEAR price: {price}$ (stale, updated {age} s ago).")
+        }
+        (Language::English, false) => {
+            format!("Current NEAR price: {price}$ (updated {age} s ago).")
         }
     }
 }
 
-pub async fn process(bot: Bot, msg: Message) -> HandlerResult {
+/// Reply when no quote could be obtained
+pub fn fetch_failed(err: &anyhow::Error, language: Language) -> String {
+    match language {
+        Language::Ukrainian => format!("Не вдалося отримати ціну NEAR: {err}"),
+        Language::English => format!("Failed to get NEAR price, due to: {err}"),
+    }
+}
+
+pub async fn process(bot: Bot, msg: Message, quotes: Quotes, language: Language) -> HandlerResult {
     info!("Getting NEAR price...");
 
-    let price = match get().await {
-        Ok(price) => price,
+    let quote = match quotes.quote(QUOTE_MAX_AGE).await {
+        Ok(quote) => quote,
         Err(err) => {
-            bot.send_message(
-                msg.chat.id,
-                format!("Failed to get NEAR price, due to: {err}"),
-            )
-            .await
-            .context("Failed to send Telegram message")?;
+            bot.send_message(msg.chat.id, fetch_failed(&err, language))
+                .await
+                .context("Failed to send Telegram message")?;
 
             return Ok(());
         }
     };
 
-    bot.send_message(msg.chat.id, format!("Current NEAR price: {price:.2}$."))
+    bot.send_message(msg.chat.id, format(&quote, language))
         .await
         .context("Failed to send Telegram message")?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rust_decimal_macros::dec;
+
+    fn scripted(price: Option<Price>, delay: Duration) -> (Scripted, Quotes) {
+        let source = Scripted {
+            delay,
+            ..Scripted::default()
+        };
+        source.set(price);
+
+        (source.clone(), Quotes::new(source))
+    }
+
+    #[tokio::test]
+    async fn serves_cached_quotes() {
+        let (source, quotes) = scripted(Some(dec!(6.2345)), Duration::ZERO);
+
+        assert_eq!(
+            quotes.quote(QUOTE_MAX_AGE).await.unwrap().price,
+            dec!(6.235)
+        );
+        source.set(Some(dec!(7)));
+        assert_eq!(
+            quotes.quote(QUOTE_MAX_AGE).await.unwrap().price,
+            dec!(6.235)
+        );
+        assert_eq!(source.fetches(), 1);
+
+        assert_eq!(quotes.quote(Duration::ZERO).await.unwrap().price, dec!(7));
+        assert_eq!(source.fetches(), 2);
+    }
+
+    #[tokio::test]
+    async fn fetches_once_for_concurrent_callers() {
+        let (source, quotes) = scripted(Some(dec!(6)), Duration::from_millis(100));
+
+        let results = futures::future::join_all((0..5).map(|_| quotes.quote(QUOTE_MAX_AGE))).await;
+
+        assert!(results.iter().all(|x| x.as_ref().unwrap().price == dec!(6)));
+        assert_eq!(source.fetches(), 1);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_cached_quote() {
+        let (source, quotes) = scripted(None, Duration::ZERO);
+        assert!(quotes.quote(QUOTE_MAX_AGE).await.is_err());
+
+        source.set(Some(dec!(6)));
+        quotes.quote(QUOTE_MAX_AGE).await.unwrap();
+        source.set(None);
+
+        let quote = quotes.quote(Duration::ZERO).await.unwrap();
+        assert_eq!(quote.price, dec!(6));
+        assert!(!quote.is_stale());
+        assert_eq!(source.fetches(), 3);
+    }
+
+    #[tokio::test]
+    async fn reports_stale_quotes() {
+        let (_, quotes) = scripted(None, Duration::ZERO);
+        let fetched_at = Instant::now()
+            .checked_sub(STALE_AFTER + Duration::from_secs(1))
+            .unwrap();
+        quotes.set(dec!(6), fetched_at).await;
+
+        let quote = quotes.quote(QUOTE_MAX_AGE).await.unwrap();
+        assert!(quote.is_stale());
+        assert!(format(&quote, Language::English).contains("stale"));
+    }
+
+    #[test]
+    fn rounds_to_tick() {
+        assert_eq!(to_tick(dec!(6.2345)), dec!(6.235));
+        assert_eq!(to_tick(dec!(0.0004)), dec!(0));
+        assert_eq!(parse(" 7,5 "), Some(dec!(7.5)));
+        assert_eq!(parse("abc"), None);
+    }
+
+    #[test]
+    fn displays_significant_digits() {
+        assert_eq!(display(dec!(7)), "7.00");
+        assert_eq!(display(dec!(7.500)), "7.50");
+        assert_eq!(display(dec!(6.123)), "6.123");
+        assert_eq!(display(dec!(0.001)), "0.001");
+        assert_eq!(format!("{:+.2}", dec!(12.5)), "+12.50");
+    }
+}
@@ -1,8 +1,7 @@
-use crate::commands::{help, price, start, triggers, Command, MyDialogue, State};
-
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::commands::{
+    admin, alerts, export, help, history, inline, limits, portfolio, price, settings, start,
+    triggers, welcome, AdminCommand, Command, Deps, State,
+};
 
 use dptree::case;
 use teloxide::{
@@ -11,41 +10,446 @@ use teloxide::{
 };
 
 pub fn process() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let command_handler = teloxide::filter_command::<Command, _>().branch(
-        case![State::Start]
-            .branch(case![Command::Help].endpoint(help::process))
-            .branch(case![Command::GetPrice].endpoint(price::process))
-            .branch(case![Command::AddTrigger].endpoint(triggers::start))
-            .branch(case![Command::DeleteTrigger].endpoint(triggers::show_trigger_to_delete))
-            .branch(case![Command::DeleteAll].endpoint(triggers::delete_all))
-            .branch(case![Command::ListTriggers].endpoint(triggers::list)),
-    );
-
-    let message_handler = Update::filter_message().branch(command_handler).branch(
-        case![State::ReceivePrice { trigger }].endpoint(
-            |bot: Bot,
-             dialogue: MyDialogue,
-             msg: Message,
-             trigger: triggers::Trigger,
-             triggers: Arc<Mutex<HashMap<ChatId, Vec<triggers::Trigger>>>>| {
-                triggers::receive_price(bot, dialogue, msg, trigger, triggers)
-            },
-        ),
-    );
+    let command_handler = teloxide::filter_command::<Command, _>()
+        .branch(dptree::filter_map_async(limits::check_rate).endpoint(limits::reject))
+        .branch(
+            case![State::Start]
+                .branch(case![Command::Start].endpoint(welcome::start))
+                .branch(case![Command::Help].endpoint(help::process))
+                .branch(case![Command::GetPrice].endpoint(price::process))
+                .branch(case![Command::AddTrigger(args)].endpoint(triggers::start))
+                .branch(case![Command::DeleteTrigger].endpoint(triggers::show_trigger_to_delete))
+                .branch(case![Command::DeleteAll].endpoint(triggers::delete_all))
+                .branch(case![Command::ListTriggers].endpoint(triggers::list))
+                .branch(case![Command::History(args)].endpoint(history::show))
+                .branch(case![Command::Position(args)].endpoint(portfolio::position))
+                .branch(case![Command::Portfolio].endpoint(portfolio::show))
+                .branch(case![Command::Settings].endpoint(settings::show))
+                .branch(case![Command::TimeZone(args)].endpoint(settings::set_time_zone))
+                .branch(case![Command::Export].endpoint(export::export))
+                .branch(case![Command::Import].endpoint(export::start_import)),
+        );
+
+    let admin_command_handler = teloxide::filter_command::<AdminCommand, _>()
+        .filter(admin::is_admin)
+        .branch(case![AdminCommand::AdminHelp].endpoint(admin::help))
+        .branch(case![AdminCommand::Stats].endpoint(admin::stats))
+        .branch(case![AdminCommand::Broadcast(text)].endpoint(admin::broadcast))
+        .branch(case![AdminCommand::DumpTriggers].endpoint(admin::dump_triggers))
+        .branch(case![AdminCommand::FeedStatus].endpoint(admin::feed_status))
+        .branch(case![AdminCommand::SetQuota(args)].endpoint(admin::set_quota));
+
+    let message_handler = Update::filter_message()
+        .branch(admin_command_handler)
+        .branch(command_handler)
+        .branch(case![State::ReceivePrice { target }].endpoint(triggers::receive_price))
+        .branch(case![State::ReceiveImport].endpoint(export::receive_import))
+        .branch(case![State::ReceiveExpiry { target }].endpoint(triggers::receive_expiry))
+        .branch(case![State::ReceiveLabel { trigger }].endpoint(triggers::receive_label));
 
     let callback_query_handler = Update::filter_callback_query()
+        .branch(dptree::filter(alerts::is_callback).endpoint(alerts::process))
+        .branch(dptree::filter(settings::is_callback).endpoint(settings::process))
+        .branch(dptree::filter(welcome::is_callback).endpoint(welcome::process))
         .branch(case![State::Start].endpoint(start))
         .branch(case![State::ReceiveTriggerType].endpoint(triggers::receive_trigger_type))
-        .branch(case![State::DeleteTrigger].endpoint(
-            |bot: Bot,
-             dialogue: MyDialogue,
-             q: CallbackQuery,
-             triggers: Arc<Mutex<HashMap<ChatId, Vec<triggers::Trigger>>>>| {
-                triggers::choose_trigger_to_delete(bot, dialogue, q, triggers)
+        .branch(case![State::ReceiveExpiry { target }].endpoint(triggers::skip_expiry))
+        .branch(case![State::ReceiveLabel { trigger }].endpoint(triggers::skip_label))
+        .branch(case![State::ConfirmConvert { trigger }].endpoint(triggers::confirm_convert))
+        .branch(case![State::DeleteTrigger].endpoint(triggers::choose_trigger_to_delete));
+
+    // Inline queries don't belong to a chat, so they are handled outside of dialogues
+    let inline_query_handler = Update::filter_inline_query().endpoint(inline::process);
+
+    dptree::entry()
+        .inspect_async(triggers::enable_chat)
+        .map(Deps::new)
+        .map_async(settings::chat_language)
+        .branch(inline_query_handler)
+        .branch(
+            dialogue::enter::<Update, InMemStorage<State>, State, _>()
+                .branch(message_handler)
+                .branch(callback_query_handler),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{
+        portfolio::{Portfolio, Position},
+        settings::{Language, Settings, WrongSide},
+        triggers::{Target, Trigger},
+    };
+    use crate::testing::{Harness, CHAT_ID};
+
+    use chrono::Utc;
+    use rust_decimal_macros::dec;
+    use teloxide::types::ChatId;
+
+    async fn add_trigger(harness: &mut Harness, target: Target) {
+        harness
+            .triggers
+            .lock()
+            .await
+            .insert(ChatId(CHAT_ID), Trigger::new(target));
+    }
+
+    #[tokio::test]
+    async fn adds_trigger_via_buttons() {
+        let mut harness = Harness::new();
+
+        harness.send("/addtrigger").await;
+        let reply = harness.last_message().await;
+        assert_eq!(reply.text(), Some("Оберіть тип тригера:"));
+        assert_eq!(
+            reply.buttons(),
+            [["Ціна більше ніж ..."], ["Ціна менше ніж ..."]]
+        );
+
+        harness
+            .press(&reply.callback_data("Ціна більше ніж ...").unwrap())
+            .await;
+        assert_eq!(harness.last_message().await.text(), Some("Вкажіть ціну:"));
+
+        harness.send("7,5").await;
+        let reply = harness.last_message().await;
+        assert_eq!(reply.buttons(), [["Без терміну"]]);
+
+        harness
+            .press(&reply.callback_data("Без терміну").unwrap())
+            .await;
+        let reply = harness.last_message().await;
+        assert_eq!(reply.buttons(), [["Без підпису"]]);
+
+        harness
+            .press(&reply.callback_data("Без підпису").unwrap())
+            .await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some("Вам прийде повідомлення якщо ціна буде більше ніж 7.50$")
+        );
+
+        let triggers = harness.triggers.lock().await;
+        assert_eq!(triggers[&ChatId(CHAT_ID)].len(), 1);
+        assert!(triggers[&ChatId(CHAT_ID)][0]
+            .target
+            .is_same(&Target::Higher(dec!(7.5))));
+    }
+
+    #[tokio::test]
+    async fn adds_labeled_triggers() {
+        let mut harness = Harness::new();
+        harness.set_price(dec!(6)).await;
+
+        harness.send("/addtrigger > 7.5 24h take  profit").await;
+        assert!(harness
+            .last_message()
+            .await
+            .text()
+            .unwrap()
+            .ends_with("— «take profit»"));
+
+        harness.send("/addtrigger").await;
+        harness
+            .press(
+                &harness
+                    .last_message()
+                    .await
+                    .callback_data("Ціна менше ніж ...")
+                    .unwrap(),
+            )
+            .await;
+        harness.send("5").await;
+        harness.send("7d").await;
+        harness.send(&"x".repeat(65)).await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some("Вкажіть підпис до 64 символів:")
+        );
+
+        harness.send("stop loss").await;
+        harness.send("/listtriggers").await;
+        let list = harness.last_message().await;
+        let list = list.text().unwrap();
+        assert!(list.contains("— «stop loss»"), "{list}");
+        assert!(list.contains("— «take profit»"));
+
+        let triggers = harness.triggers.lock().await;
+        assert_eq!(
+            triggers[&ChatId(CHAT_ID)]
+                .iter()
+                .map(|x| x.label.as_deref())
+                .collect::<Vec<_>>(),
+            [Some("stop loss"), Some("take profit")]
+        );
+    }
+
+    #[tokio::test]
+    async fn handles_triggers_already_reached() {
+        let mut harness = Harness::new();
+        harness.set_price(dec!(6)).await;
+
+        harness.settings.lock().await.insert(
+            ChatId(CHAT_ID),
+            Settings {
+                wrong_side: WrongSide::Reject,
+                ..Settings::default()
+            },
+        );
+
+        harness.send("/addtrigger < 7").await;
+        assert!(harness
+            .last_message()
+            .await
+            .text()
+            .unwrap()
+            .contains("Тригер не додано"));
+        assert!(harness.triggers.lock().await.is_empty());
+
+        harness
+            .settings
+            .lock()
+            .await
+            .get_mut(&ChatId(CHAT_ID))
+            .unwrap()
+            .wrong_side = WrongSide::Convert;
+
+        harness.send("/addtrigger < 7").await;
+        let reply = harness.last_message().await;
+        assert_eq!(
+            reply.text(),
+            Some(
+                "Ціна вже 6.00$, тому тригер «менше ніж 7.00$» спрацював би одразу. \
+                 Додати натомість тригер «більше ніж 7.00$»?"
+            )
+        );
+        assert_eq!(reply.buttons(), [["Додати"], ["Скасувати"]]);
+
+        harness
+            .press(&reply.callback_data("Скасувати").unwrap())
+            .await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some("Тригер не додано")
+        );
+        assert!(harness.triggers.lock().await.is_empty());
+
+        harness.send("/addtrigger < 7").await;
+        let reply = harness.last_message().await;
+        harness.press(&reply.callback_data("Додати").unwrap()).await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some("Вам прийде повідомлення якщо ціна буде більше ніж 7.00$")
+        );
+
+        harness
+            .settings
+            .lock()
+            .await
+            .get_mut(&ChatId(CHAT_ID))
+            .unwrap()
+            .wrong_side = WrongSide::ArmOnCrossing;
+
+        // Every trigger waits for the engine to arm it, whichever side of the threshold the price is on
+        harness.send("/addtrigger > 5").await;
+        harness.last_message().await;
+        harness.send("/addtrigger < 5").await;
+        harness.last_message().await;
+
+        let triggers = harness.triggers.lock().await;
+        let triggers = &triggers[&ChatId(CHAT_ID)];
+        assert!(triggers
+            .iter()
+            .any(|x| x.target.is_same(&Target::Higher(dec!(7))) && x.armed));
+        assert!(triggers
+            .iter()
+            .any(|x| x.target.is_same(&Target::Higher(dec!(5))) && !x.armed));
+        assert!(triggers
+            .iter()
+            .any(|x| x.target.is_same(&Target::Lower(dec!(5))) && !x.armed));
+    }
+
+    #[tokio::test]
+    async fn checks_portfolio_triggers_already_reached() {
+        let mut harness = Harness::new();
+        harness.set_price(dec!(6)).await;
+
+        harness.settings.lock().await.insert(
+            ChatId(CHAT_ID),
+            Settings {
+                wrong_side: WrongSide::Reject,
+                ..Settings::default()
+            },
+        );
+        harness.portfolios.lock().await.insert(
+            ChatId(CHAT_ID),
+            Portfolio {
+                positions: vec![Position {
+                    amount: dec!(100),
+                    entry_price: dec!(5),
+                    opened_at: Utc::now(),
+                }],
             },
-        ));
+        );
+
+        harness.send("/addtrigger value < 700").await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some(
+                "Вартість портфеля вже 600.00$, тому тригер «вартість портфеля менше ніж 700.00$» \
+                 спрацював би одразу. Тригер не додано, змінити поведінку можна в /settings"
+            )
+        );
+
+        harness.send("/addtrigger pnl > 10").await;
+        assert!(harness
+            .last_message()
+            .await
+            .text()
+            .unwrap()
+            .starts_with("P&L портфеля вже +20.00%"));
+
+        harness.send("/addtrigger pnl < 10").await;
+        harness.last_message().await;
+
+        let triggers = harness.triggers.lock().await;
+        let triggers = &triggers[&ChatId(CHAT_ID)];
+        assert_eq!(triggers.len(), 1);
+        assert!(triggers[0].armed);
+    }
+
+    #[tokio::test]
+    async fn rejects_triggers_over_quota() {
+        let mut harness = Harness::new();
+        harness.set_price(dec!(6)).await;
+        harness
+            .limits
+            .set_quota(ChatId(CHAT_ID), Some(1))
+            .await
+            .unwrap();
+
+        harness.send("/addtrigger > 100").await;
+        harness.last_message().await;
+        harness.send("/addtrigger > 200").await;
+
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some(
+                "Тригер не додано: досягнуто ліміт тригерів чату (1). \
+                 Видалити непотрібні тригери можна через /deletetrigger"
+            )
+        );
+        assert_eq!(harness.triggers.lock().await[&ChatId(CHAT_ID)].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deletes_selected_trigger() {
+        let mut harness = Harness::new();
+        add_trigger(&mut harness, Target::Lower(dec!(5))).await;
+        add_trigger(&mut harness, Target::Higher(dec!(8))).await;
+
+        harness.send("/deletetrigger").await;
+        let reply = harness.last_message().await;
+        assert_eq!(reply.text(), Some("Оберіть тригер для видалення:"));
+        assert_eq!(reply.buttons(), [["менше ніж 5.00$", "більше ніж 8.00$"]]);
+
+        harness
+            .press(&reply.callback_data("менше ніж 5.00$").unwrap())
+            .await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some("Тригер на 5.00$ був видалений")
+        );
+
+        let triggers = harness.triggers.lock().await;
+        assert_eq!(triggers[&ChatId(CHAT_ID)].len(), 1);
+        assert!(triggers[&ChatId(CHAT_ID)][0]
+            .target
+            .is_same(&Target::Higher(dec!(8))));
+    }
+
+    #[tokio::test]
+    async fn deletes_all_triggers() {
+        let mut harness = Harness::new();
+
+        harness.send("/deleteall").await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some("У вас наразі немає тригерів")
+        );
+
+        add_trigger(&mut harness, Target::Lower(dec!(5))).await;
+        add_trigger(&mut harness, Target::Higher(dec!(8))).await;
+
+        harness.send("/deleteall").await;
+        assert_eq!(
+            harness.last_message().await.text(),
+            Some("Всі тригери були видалені")
+        );
+        assert!(harness.triggers.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn onboards_new_and_returning_users() {
+        let mut harness = Harness::new();
+        harness.set_price(dec!(6)).await;
+
+        harness.send("/start").await;
+        let reply = harness.last_message().await;
+        assert!(reply.text().unwrap().starts_with("Вітаю!"));
+        assert!(reply.text().unwrap().contains("/help"));
+
+        let buttons = reply.buttons();
+        assert_eq!(buttons.len(), 3);
+        assert!(buttons[0][0].starts_with("-10%"));
+        assert!(buttons[1][1].starts_with("+10%"));
+        assert_eq!(buttons[2], ["Українська", "English"]);
+
+        harness
+            .press(&reply.callback_data(&buttons[0][0]).unwrap())
+            .await;
+        assert!(harness
+            .last_message()
+            .await
+            .text()
+            .unwrap()
+            .starts_with("Вам прийде повідомлення якщо ціна буде менше ніж"));
+        assert!(harness.triggers.lock().await[&ChatId(CHAT_ID)][0]
+            .target
+            .is_lower());
+
+        // The chosen language is kept for replies and the chat's command menu
+        harness
+            .press(&reply.callback_data("English").unwrap())
+            .await;
+        let requests = harness.take_requests().await;
+        assert!(requests
+            .iter()
+            .any(|x| x.text().is_some_and(|x| x.starts_with("Welcome back!"))));
+        assert!(requests.iter().any(|x| {
+            x.method.eq_ignore_ascii_case("setmycommands")
+                && x.body["scope"]["type"] == "chat"
+                && x.body["scope"]["chat_id"] == CHAT_ID
+        }));
+        assert_eq!(
+            harness.settings.lock().await[&ChatId(CHAT_ID)].language,
+            Language::English
+        );
+
+        harness.send("/start").await;
+        let summary = harness.last_message().await;
+        assert!(summary.text().unwrap().starts_with("Welcome back!"));
+        assert!(summary
+            .text()
+            .unwrap()
+            .contains("Your triggers (1):\nbelow"));
 
-    dialogue::enter::<Update, InMemStorage<State>, State, _>()
-        .branch(message_handler)
-        .branch(callback_query_handler)
+        harness.send("/listtriggers").await;
+        assert!(harness
+            .last_message()
+            .await
+            .text()
+            .unwrap()
+            .starts_with("Notify me when the price is:"));
+    }
 }
@@ -0,0 +1,846 @@
+use crate::commands::{
+    callback::{Callback, SettingsAction},
+    menu,
+    notifier::{self, Delivery, Notifier},
+    storage::Storage,
+    HandlerResult,
+};
+use crate::config::Config;
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{interval, Duration};
+
+use teloxide::{prelude::*, types::InlineKeyboardMarkup};
+
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Alerts kept for the morning summary of a chat, older ones are dropped first
+const MAX_DEFERRED: usize = 100;
+/// Format version of settings.bak
+const BACKUP_VERSION: u16 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuietMode {
+    Silent,
+    Summary,
+}
+
+/// What to do with a new trigger whose threshold the price has already reached
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WrongSide {
+    Reject,
+    /// Propose the trigger in the opposite direction and add it once the chat confirms
+    Convert,
+    /// Add every trigger unarmed, the engine arms it on the first tick its threshold is not reached
+    #[default]
+    ArmOnCrossing,
+}
+
+/// Language of the replies to a chat, chosen on /start or in /settings
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Ukrainian,
+    English,
+}
+
+impl Language {
+    /// Language for a Telegram client language code, the bot speaks Ukrainian unless the client is English
+    pub fn from_code(code: Option<&str>) -> Self {
+        match code {
+            Some(code) if code.starts_with("en") => Self::English,
+            _ => Self::Ukrainian,
+        }
+    }
+
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::Ukrainian => "uk",
+            Self::English => "en",
+        }
+    }
+
+    pub fn decode(code: &str) -> Option<Self> {
+        match code {
+            "uk" => Some(Self::Ukrainian),
+            "en" => Some(Self::English),
+            _ => None,
+        }
+    }
+
+    /// Picks the text in this language out of its translations
+    pub const fn pick<'a>(self, uk: &'a str, en: &'a str) -> &'a str {
+        match self {
+            Self::Ukrainian => uk,
+            Self::English => en,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Settings {
+    /// IANA time zone, so that quiet hours and dates follow daylight saving time
+    pub time_zone: Tz,
+    pub quiet_hours: bool,
+    pub quiet_start: u8,
+    pub quiet_end: u8,
+    pub quiet_mode: QuietMode,
+    pub deferred: Vec<String>,
+    /// Deferred alerts dropped since the last summary because of [`MAX_DEFERRED`]
+    pub dropped: usize,
+    pub wrong_side: WrongSide,
+    pub language: Language,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            time_zone: Tz::UTC,
+            quiet_hours: false,
+            quiet_start: 23,
+            quiet_end: 8,
+            quiet_mode: QuietMode::Silent,
+            deferred: Vec::new(),
+            dropped: 0,
+            wrong_side: WrongSide::default(),
+            language: Language::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn local_hour(&self, now: DateTime<Utc>) -> u8 {
+        now.with_timezone(&self.time_zone).hour() as u8
+    }
+
+    /// Keeps an alert for the summary, dropping the oldest one once [`MAX_DEFERRED`] are kept
+    pub fn defer(&mut self, alert: String) {
+        if self.deferred.len() >= MAX_DEFERRED {
+            self.deferred.remove(0);
+            self.dropped += 1;
+        }
+
+        self.deferred.push(alert);
+    }
+
+    /// Returns how alerts should be delivered if `now` falls into the chat's quiet hours
+    pub fn quiet_mode(&self, now: DateTime<Utc>) -> Option<QuietMode> {
+        if !self.quiet_hours || self.quiet_start == self.quiet_end {
+            return None;
+        }
+
+        let hour = self.local_hour(now);
+        let is_quiet = if self.quiet_start < self.quiet_end {
+            (self.quiet_start..self.quiet_end).contains(&hour)
+        } else {
+            hour >= self.quiet_start || hour < self.quiet_end
+        };
+
+        is_quiet.then_some(self.quiet_mode)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.quiet_start < 24 && self.quiet_end < 24
+    }
+
+    fn apply(&mut self, action: SettingsAction) {
+        let shift_hour = |hour: u8, delta: i16| (i16::from(hour) + delta).rem_euclid(24) as u8;
+
+        match action {
+            SettingsAction::ToggleQuietHours => self.quiet_hours = !self.quiet_hours,
+            SettingsAction::StartDown => self.quiet_start = shift_hour(self.quiet_start, -1),
+            SettingsAction::StartUp => self.quiet_start = shift_hour(self.quiet_start, 1),
+            SettingsAction::EndDown => self.quiet_end = shift_hour(self.quiet_end, -1),
+            SettingsAction::EndUp => self.quiet_end = shift_hour(self.quiet_end, 1),
+            SettingsAction::ToggleMode => {
+                self.quiet_mode = match self.quiet_mode {
+                    QuietMode::Silent => QuietMode::Summary,
+                    QuietMode::Summary => QuietMode::Silent,
+                };
+            }
+            SettingsAction::ToggleLanguage => {
+                self.language = match self.language {
+                    Language::Ukrainian => Language::English,
+                    Language::English => Language::Ukrainian,
+                };
+            }
+            SettingsAction::CycleWrongSide => {
+                self.wrong_side = match self.wrong_side {
+                    WrongSide::Reject => WrongSide::Convert,
+                    WrongSide::Convert => WrongSide::ArmOnCrossing,
+                    WrongSide::ArmOnCrossing => WrongSide::Reject,
+                };
+            }
+        }
+    }
+
+    fn describe(&self, now: DateTime<Utc>) -> String {
+        let language = self.language;
+        let quiet_hours = if self.quiet_hours {
+            language.pick("увімкнено", "on")
+        } else {
+            language.pick("вимкнено", "off")
+        };
+        let quiet_mode = match self.quiet_mode {
+            QuietMode::Silent => language.pick("без звуку", "silent"),
+            QuietMode::Summary => language.pick("ранкове зведення", "morning summary"),
+        };
+        let wrong_side = match self.wrong_side {
+            WrongSide::Reject => language.pick("відхиляти", "reject"),
+            WrongSide::Convert => language.pick("змінювати напрямок", "flip the direction"),
+            WrongSide::ArmOnCrossing => {
+                language.pick("чекати перетину ціни", "wait for the price to cross")
+            }
+        };
+        let (time_zone, offset) = (
+            self.time_zone,
+            now.with_timezone(&self.time_zone).format("%:z"),
+        );
+        let (start, end) = (self.quiet_start, self.quiet_end);
+
+        match language {
+            Language::Ukrainian => format!(
+                "Налаштування сповіщень:\n\
+                 Часовий пояс: {time_zone} (UTC{offset}), змінити: /timezone\n\
+                 Тихі години: {start:02}:00–{end:02}:00 ({quiet_hours})\n\
+                 Режим тихих годин: {quiet_mode}\n\
+                 Тригери, які спрацювали б одразу: {wrong_side}\n\
+                 Мова: українська"
+            ),
+            Language::English => format!(
+                "Notification settings:\n\
+                 Time zone: {time_zone} (UTC{offset}), change: /timezone\n\
+                 Quiet hours: {start:02}:00–{end:02}:00 ({quiet_hours})\n\
+                 Quiet hours mode: {quiet_mode}\n\
+                 Triggers which would fire at once: {wrong_side}\n\
+                 Language: English"
+            ),
+        }
+    }
+
+    fn keyboard(&self) -> InlineKeyboardMarkup {
+        let language = self.language;
+        let button = |uk: &str, en: &str, action: SettingsAction| {
+            Callback::Settings(action).button(language.pick(uk, en))
+        };
+
+        InlineKeyboardMarkup::new([
+            vec![if self.quiet_hours {
+                button(
+                    "Вимкнути тихі години",
+                    "Turn quiet hours off",
+                    SettingsAction::ToggleQuietHours,
+                )
+            } else {
+                button(
+                    "Увімкнути тихі години",
+                    "Turn quiet hours on",
+                    SettingsAction::ToggleQuietHours,
+                )
+            }],
+            vec![
+                button("Початок -1", "Start -1", SettingsAction::StartDown),
+                button("Початок +1", "Start +1", SettingsAction::StartUp),
+            ],
+            vec![
+                button("Кінець -1", "End -1", SettingsAction::EndDown),
+                button("Кінець +1", "End +1", SettingsAction::EndUp),
+            ],
+            vec![match self.quiet_mode {
+                QuietMode::Silent => button(
+                    "Режим: ранкове зведення",
+                    "Mode: morning summary",
+                    SettingsAction::ToggleMode,
+                ),
+                QuietMode::Summary => button(
+                    "Режим: без звуку",
+                    "Mode: silent",
+                    SettingsAction::ToggleMode,
+                ),
+            }],
+            vec![button(
+                "Тригери, які спрацювали б одразу",
+                "Triggers which would fire at once",
+                SettingsAction::CycleWrongSide,
+            )],
+            // Named in the language it switches to
+            vec![button(
+                "English",
+                "Українська",
+                SettingsAction::ToggleLanguage,
+            )],
+        ])
+    }
+}
+
+/// Language of the chat an update comes from, chats without settings get the one of the user's Telegram client
+pub async fn chat_language(
+    update: Update,
+    settings: Arc<Mutex<HashMap<ChatId, Settings>>>,
+) -> Language {
+    let chat_language = match update.chat() {
+        Some(chat) => settings.lock().await.get(&chat.id).map(|x| x.language),
+        None => None,
+    };
+
+    chat_language.unwrap_or_else(|| {
+        Language::from_code(update.user().and_then(|x| x.language_code.as_deref()))
+    })
+}
+
+/// Language of the replies to a chat outside of an update, e.g. in alerts
+pub async fn language(settings: &Mutex<HashMap<ChatId, Settings>>, chat_id: ChatId) -> Language {
+    settings
+        .lock()
+        .await
+        .get(&chat_id)
+        .map(|x| x.language)
+        .unwrap_or_default()
+}
+
+/// Settings of a chat, created in the chat's `language` if it has none yet
+pub fn chat_entry(
+    settings: &mut HashMap<ChatId, Settings>,
+    chat_id: ChatId,
+    language: Language,
+) -> &mut Settings {
+    settings.entry(chat_id).or_insert_with(|| Settings {
+        language,
+        ..Settings::default()
+    })
+}
+
+pub async fn show(
+    bot: Bot,
+    msg: Message,
+    settings: Arc<Mutex<HashMap<ChatId, Settings>>>,
+    language: Language,
+) -> HandlerResult {
+    info!("Showing settings...");
+
+    let chat_settings = settings
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .cloned()
+        .unwrap_or_else(|| Settings {
+            language,
+            ..Settings::default()
+        });
+
+    bot.send_message(msg.chat.id, chat_settings.describe(Utc::now()))
+        .reply_markup(chat_settings.keyboard())
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    matches!(Callback::from_query(&q), Some(Callback::Settings(_)))
+}
+
+pub async fn process(
+    bot: Bot,
+    q: CallbackQuery,
+    settings: Arc<Mutex<HashMap<ChatId, Settings>>>,
+    storage: Storage,
+    config: Config,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving settings action...");
+
+    bot.answer_callback_query(q.id.clone())
+        .await
+        .context("Failed to answer callback query")?;
+
+    let (Some(Callback::Settings(action)), Some(message)) = (Callback::from_query(&q), q.message)
+    else {
+        warn!("Invalid settings callback query: {:?}", q.data);
+        return Ok(());
+    };
+
+    let chat_settings = {
+        let mut locked_settings = settings.lock().await;
+        let chat_settings = chat_entry(&mut locked_settings, message.chat.id, language);
+        chat_settings.apply(action);
+        let chat_settings = chat_settings.clone();
+
+        if let Err(err) = backup(&storage, &locked_settings) {
+            error!("Failed to backup settings, due to: {}", err);
+        }
+
+        chat_settings
+    };
+
+    if action == SettingsAction::ToggleLanguage {
+        menu::register_chat(&bot, &message.chat, &config, chat_settings.language).await;
+    }
+
+    bot.edit_message_text(
+        message.chat.id,
+        message.id,
+        chat_settings.describe(Utc::now()),
+    )
+    .reply_markup(chat_settings.keyboard())
+    .await
+    .context("Failed to edit Telegram message")?;
+
+    Ok(())
+}
+
+/// Sets the time zone of the chat, e.g. `/timezone Europe/Kyiv`
+pub async fn set_time_zone(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    settings: Arc<Mutex<HashMap<ChatId, Settings>>>,
+    storage: Storage,
+    language: Language,
+) -> HandlerResult {
+    info!("Setting time zone...");
+
+    let Ok(time_zone) = args.trim().parse::<Tz>() else {
+        warn!("User provided invalid time zone: {args:?}");
+
+        bot.send_message(
+            msg.chat.id,
+            language.pick(
+                "Формат: /timezone <пояс>, наприклад /timezone Europe/Kyiv або /timezone UTC",
+                "Usage: /timezone <zone>, e.g. /timezone Europe/Kyiv or /timezone UTC",
+            ),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    };
+
+    {
+        let mut locked_settings = settings.lock().await;
+        chat_entry(&mut locked_settings, msg.chat.id, language).time_zone = time_zone;
+
+        if let Err(err) = backup(&storage, &locked_settings) {
+            error!("Failed to backup settings, due to: {}", err);
+        }
+    }
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "{} {time_zone} (UTC{})",
+            language.pick("Часовий пояс змінено на", "Time zone changed to"),
+            Utc::now().with_timezone(&time_zone).format("%:z")
+        ),
+    )
+    .await
+    .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+const SUMMARY_HEADERS: [&str; 2] = [
+    "Сповіщення під час тихих годин:",
+    "Alerts during quiet hours:",
+];
+
+/// Alerts deferred by a chat whose quiet hours are over
+struct Summary {
+    chat_id: ChatId,
+    deferred: Vec<String>,
+    dropped: usize,
+    language: Language,
+}
+
+impl Summary {
+    /// Header and alerts, split into as many messages as the length limit requires
+    fn messages(&self) -> Vec<String> {
+        let [uk, en] = SUMMARY_HEADERS;
+        let mut header = String::from(self.language.pick(uk, en));
+
+        if self.dropped > 0 {
+            header.push_str(&format!(
+                "\n({} {})",
+                self.language
+                    .pick("найстаріші пропущено:", "oldest ones dropped:"),
+                self.dropped
+            ));
+        }
+
+        notifier::split_message(
+            std::iter::once(header).chain(self.deferred.iter().cloned()),
+            "\n\n",
+        )
+    }
+}
+
+/// Takes the deferred alerts of the chats whose quiet hours are over at `now`
+fn take_summaries(settings: &mut HashMap<ChatId, Settings>, now: DateTime<Utc>) -> Vec<Summary> {
+    settings
+        .iter_mut()
+        .filter(|(_, x)| !x.deferred.is_empty() && x.quiet_mode(now).is_none())
+        .map(|(chat_id, x)| Summary {
+            chat_id: *chat_id,
+            deferred: std::mem::take(&mut x.deferred),
+            dropped: std::mem::take(&mut x.dropped),
+            language: x.language,
+        })
+        .collect()
+}
+
+/// Sends a summary, returning the messages that couldn't be sent because of a network error
+async fn send_summary(notifier: &Notifier, summary: &Summary) -> Vec<String> {
+    let messages = summary.messages();
+
+    for (index, message) in messages.iter().enumerate() {
+        match notifier.send(summary.chat_id, message, None, false).await {
+            Ok(Delivery::Sent) => {}
+            // Nobody would ever read the rest
+            Ok(Delivery::ChatUnavailable) => return Vec::new(),
+            Err(err) => {
+                error!("Failed to send summary to chat {}: {err}", summary.chat_id);
+                return messages[index..].to_vec();
+            }
+        }
+    }
+
+    Vec::new()
+}
+
+/// Keeps the messages of a summary which couldn't be sent ahead of the alerts deferred meanwhile,
+/// so they are retried on the next tick. The count of dropped alerts is already in their text
+fn requeue(chat_settings: &mut Settings, unsent: Vec<String>) {
+    let unsent = unsent
+        .into_iter()
+        .map(|x| {
+            match SUMMARY_HEADERS
+                .iter()
+                .find_map(|header| x.strip_prefix(header))
+            {
+                Some(rest) => rest.trim_start().to_owned(),
+                None => x,
+            }
+        })
+        .filter(|x| !x.is_empty());
+    let deferred = std::mem::take(&mut chat_settings.deferred);
+
+    for alert in unsent.chain(deferred) {
+        chat_settings.defer(alert);
+    }
+}
+
+/// Sends alerts deferred during quiet hours once the chat's quiet hours are over
+pub async fn deliver_summaries(
+    notifier: Notifier,
+    settings: Arc<Mutex<HashMap<ChatId, Settings>>>,
+    storage: Storage,
+) {
+    let mut interval = interval(SUMMARY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let mut locked_settings = settings.lock().await;
+        let summaries = take_summaries(&mut locked_settings, Utc::now());
+
+        if summaries.is_empty() {
+            continue;
+        }
+
+        drop(locked_settings);
+
+        for summary in summaries {
+            info!(
+                "Sending summary of {} alerts to chat {}",
+                summary.deferred.len(),
+                summary.chat_id
+            );
+
+            let unsent = send_summary(&notifier, &summary).await;
+
+            if !unsent.is_empty() {
+                let mut locked_settings = settings.lock().await;
+                requeue(locked_settings.entry(summary.chat_id).or_default(), unsent);
+            }
+        }
+
+        if let Err(err) = backup(&storage, &*settings.lock().await) {
+            error!("Failed to backup settings, due to: {}", err);
+        }
+    }
+}
+
+pub fn backup(storage: &Storage, settings: &HashMap<ChatId, Settings>) -> Result<()> {
+    info!("Backing up settings...");
+
+    storage.save("settings.bak", BACKUP_VERSION, settings)
+}
+
+pub fn restore(storage: &Storage) -> Result<HashMap<ChatId, Settings>> {
+    info!("Restoring settings...");
+
+    storage.load("settings.bak", BACKUP_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_storage, Harness, CHAT_ID};
+
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, hour, 30, 0).unwrap()
+    }
+
+    fn quiet(quiet_mode: QuietMode) -> Settings {
+        Settings {
+            quiet_hours: true,
+            quiet_mode,
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn wraps_quiet_hours_past_midnight() {
+        let settings = quiet(QuietMode::Silent);
+
+        for hour in [23, 0, 3, 7] {
+            assert!(
+                settings.quiet_mode(at(hour)) == Some(QuietMode::Silent),
+                "{hour}"
+            );
+        }
+        for hour in [8, 12, 22] {
+            assert!(settings.quiet_mode(at(hour)).is_none(), "{hour}");
+        }
+
+        let daytime = Settings {
+            quiet_start: 13,
+            quiet_end: 15,
+            ..settings.clone()
+        };
+        assert!(daytime.quiet_mode(at(12)).is_none());
+        assert!(daytime.quiet_mode(at(14)).is_some());
+        assert!(daytime.quiet_mode(at(15)).is_none());
+
+        let disabled = Settings {
+            quiet_hours: false,
+            ..settings.clone()
+        };
+        assert!(disabled.quiet_mode(at(0)).is_none());
+
+        let empty = Settings {
+            quiet_end: 23,
+            ..settings
+        };
+        assert!(empty.quiet_mode(at(23)).is_none());
+    }
+
+    #[test]
+    fn applies_time_zone_to_quiet_hours() {
+        let kyiv = Settings {
+            time_zone: Tz::Europe__Kyiv,
+            ..quiet(QuietMode::Silent)
+        };
+        // 21:30 UTC is 23:30 in winter Kyiv, 06:30 UTC is 08:30
+        assert!(kyiv.quiet_mode(at(20)).is_none());
+        assert!(kyiv.quiet_mode(at(21)).is_some());
+        assert!(kyiv.quiet_mode(at(5)).is_some());
+        assert!(kyiv.quiet_mode(at(6)).is_none());
+
+        // Daylight saving time moves the morning an hour earlier in UTC
+        let summer = |hour| Utc.with_ymd_and_hms(2026, 7, 1, hour, 30, 0).unwrap();
+        assert!(kyiv.quiet_mode(summer(20)).is_some());
+        assert!(kyiv.quiet_mode(summer(5)).is_none());
+
+        let west = Settings {
+            time_zone: Tz::America__New_York,
+            ..quiet(QuietMode::Silent)
+        };
+        // 04:30 UTC is 23:30 in New York, 13:30 UTC is 08:30
+        assert!(west.quiet_mode(at(3)).is_none());
+        assert!(west.quiet_mode(at(4)).is_some());
+        assert!(west.quiet_mode(at(12)).is_some());
+        assert!(west.quiet_mode(at(13)).is_none());
+
+        // 17:30 UTC is 23:00 at UTC+5:30, 02:30 UTC is 08:00
+        let india = Settings {
+            time_zone: Tz::Asia__Kolkata,
+            ..quiet(QuietMode::Silent)
+        };
+        assert!(india.quiet_mode(at(17)).is_some());
+        assert!(india.quiet_mode(at(1)).is_some());
+        assert!(india.quiet_mode(at(2)).is_none());
+    }
+
+    #[test]
+    fn delivers_deferred_alerts_in_the_morning() {
+        let mut settings = HashMap::from([
+            (
+                ChatId(1),
+                Settings {
+                    deferred: vec![String::from("first"), String::from("second")],
+                    ..quiet(QuietMode::Summary)
+                },
+            ),
+            (
+                ChatId(2),
+                Settings {
+                    time_zone: Tz::America__New_York,
+                    deferred: vec![String::from("third")],
+                    ..quiet(QuietMode::Summary)
+                },
+            ),
+            (ChatId(3), quiet(QuietMode::Summary)),
+        ]);
+        let mut take = |now| {
+            take_summaries(&mut settings, now)
+                .into_iter()
+                .map(|x| (x.chat_id, x.deferred))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(take(at(7)).is_empty());
+        assert_eq!(
+            take(at(8)),
+            [(
+                ChatId(1),
+                vec![String::from("first"), String::from("second")]
+            )]
+        );
+
+        // 08:30 UTC is still night in New York
+        assert!(take(at(8)).is_empty());
+        assert_eq!(take(at(13)), [(ChatId(2), vec![String::from("third")])]);
+    }
+
+    #[test]
+    fn drops_oldest_deferred_alerts() {
+        let mut settings = Settings::default();
+
+        for index in 0..=MAX_DEFERRED {
+            settings.defer(index.to_string());
+        }
+
+        assert_eq!(settings.deferred.len(), MAX_DEFERRED);
+        assert_eq!(settings.deferred[0], "1");
+        assert_eq!(settings.dropped, 1);
+
+        let summary = Summary {
+            chat_id: ChatId(1),
+            deferred: settings.deferred,
+            dropped: settings.dropped,
+            language: Language::Ukrainian,
+        };
+        assert!(summary.messages()[0]
+            .starts_with("Сповіщення під час тихих годин:\n(найстаріші пропущено: 1)\n\n1\n\n2"));
+    }
+
+    #[tokio::test]
+    async fn requeues_unsent_summary() {
+        let harness = Harness::new();
+        let summary = Summary {
+            chat_id: ChatId(CHAT_ID),
+            deferred: vec!["a".repeat(3000), "b".repeat(3000)],
+            dropped: 0,
+            language: Language::English,
+        };
+
+        // A long summary is split across messages
+        assert_eq!(
+            send_summary(&harness.notifier, &summary).await,
+            Vec::<String>::new()
+        );
+        let requests = harness.take_requests().await;
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].text().unwrap().starts_with('b'));
+
+        harness
+            .fail_next(serde_json::json!({
+                "ok": false,
+                "error_code": 400,
+                "description": "Bad Request: message text is empty",
+            }))
+            .await;
+        let unsent = send_summary(&harness.notifier, &summary).await;
+        assert_eq!(unsent.len(), 2);
+
+        let mut settings = Settings {
+            deferred: vec![String::from("new")],
+            ..Settings::default()
+        };
+        requeue(&mut settings, unsent);
+
+        assert_eq!(
+            settings.deferred,
+            ["a".repeat(3000), "b".repeat(3000), String::from("new")]
+        );
+    }
+
+    #[tokio::test]
+    async fn switches_language() {
+        let mut harness = Harness::new();
+
+        harness.send("/settings").await;
+        let reply = harness.last_message().await;
+        assert!(reply.text().unwrap().contains("Мова: українська"));
+
+        harness
+            .press(&reply.callback_data("English").unwrap())
+            .await;
+        let requests = harness.take_requests().await;
+        assert!(requests
+            .iter()
+            .any(|x| x.text().is_some_and(|x| x.contains("Language: English"))));
+        assert!(requests.iter().any(|x| {
+            x.method.eq_ignore_ascii_case("setmycommands") && x.body["scope"]["type"] == "chat"
+        }));
+
+        harness.send("/help").await;
+        assert!(harness
+            .last_message()
+            .await
+            .text()
+            .unwrap()
+            .starts_with("These commands are supported:"));
+    }
+
+    #[test]
+    fn round_trips_backup() {
+        let storage = temp_storage();
+        let settings = HashMap::from([(
+            ChatId(1),
+            Settings {
+                time_zone: Tz::Asia__Kathmandu,
+                quiet_hours: true,
+                quiet_mode: QuietMode::Summary,
+                deferred: vec![String::from("alert")],
+                dropped: 2,
+                wrong_side: WrongSide::Reject,
+                ..Settings::default()
+            },
+        )]);
+
+        backup(&storage, &settings).unwrap();
+        let restored = restore(&storage).unwrap();
+
+        let chat_settings = &restored[&ChatId(1)];
+        assert_eq!(chat_settings.time_zone, Tz::Asia__Kathmandu);
+        assert!(chat_settings.quiet_hours);
+        assert!(chat_settings.quiet_mode == QuietMode::Summary);
+        assert_eq!(chat_settings.deferred, ["alert"]);
+        assert_eq!(chat_settings.dropped, 2);
+        assert_eq!(chat_settings.wrong_side, WrongSide::Reject);
+    }
+}
@@ -0,0 +1,131 @@
+//! Directory the backup files are kept in
+
+use anyhow::{bail, Context, Result};
+
+use bincode::{deserialize, serialize};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Starts every backup written with a format header, files without it were written by the first release
+const MAGIC: &[u8; 4] = b"NPTB";
+
+/// Contents of a backup file which may still be in the format of the first release
+pub enum Backup<T> {
+    Current(T),
+    /// File from before backups had a header, left to the caller to decode
+    Baseline(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Storage {
+    dir: PathBuf,
+}
+
+impl Storage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Location of a backup file, for tests which prepare files by hand
+    #[cfg(test)]
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    fn write(&self, name: &str, content: &[u8]) -> Result<()> {
+        let path = self.dir.join(name);
+
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>> {
+        let path = self.dir.join(name);
+
+        fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    /// Writes `value` prefixed with the header and the format `version` of the file
+    pub fn save<T: Serialize>(&self, name: &str, version: u16, value: &T) -> Result<()> {
+        self.write(name, &encode(version, value)?)
+    }
+
+    /// Reads a file saved with [`Storage::save`], refusing format versions other than `version`
+    pub fn load<T: DeserializeOwned>(&self, name: &str, version: u16) -> Result<T> {
+        match self.load_or_baseline(name, version)? {
+            Backup::Current(value) => Ok(value),
+            Backup::Baseline(_) => bail!("{name} has no format header"),
+        }
+    }
+
+    /// Like [`Storage::load`], but hands files without a header back for conversion
+    pub fn load_or_baseline<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        version: u16,
+    ) -> Result<Backup<T>> {
+        decode(&self.read(name)?, version).with_context(|| format!("Failed to restore {name}"))
+    }
+}
+
+pub fn encode<T: Serialize>(version: u16, value: &T) -> Result<Vec<u8>> {
+    let mut content = MAGIC.to_vec();
+    content.extend_from_slice(&version.to_le_bytes());
+    content.extend(serialize(value)?);
+
+    Ok(content)
+}
+
+pub fn decode<T: DeserializeOwned>(content: &[u8], version: u16) -> Result<Backup<T>> {
+    let Some(content) = content.strip_prefix(MAGIC) else {
+        return Ok(Backup::Baseline(content.to_vec()));
+    };
+
+    let Some((found, content)) = content.split_first_chunk::<2>() else {
+        bail!("Truncated backup header");
+    };
+
+    let found = u16::from_le_bytes(*found);
+    if found != version {
+        bail!("Unsupported backup format version {found}, expected {version}");
+    }
+
+    Ok(Backup::Current(deserialize(content)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_storage;
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn round_trips_versioned_backups() {
+        let storage = temp_storage();
+        let value = HashMap::from([(1_i64, String::from("a"))]);
+
+        storage.save("test.bak", 3, &value).unwrap();
+
+        let restored: HashMap<i64, String> = storage.load("test.bak", 3).unwrap();
+        assert_eq!(restored, value);
+
+        assert!(storage.load::<HashMap<i64, String>>("test.bak", 4).is_err());
+    }
+
+    #[test]
+    fn passes_baseline_files_through() {
+        let content = serialize(&5_u32).unwrap();
+
+        match decode::<u32>(&content, 1).unwrap() {
+            Backup::Baseline(x) => assert_eq!(x, content),
+            Backup::Current(_) => panic!("Headerless file was decoded as current"),
+        }
+
+        let storage = temp_storage();
+        storage.write("test.bak", &content).unwrap();
+        assert!(storage.load::<u32>("test.bak", 1).is_err());
+
+        assert!(decode::<u32>(b"NPTB\x01", 1).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+//! In-memory trigger storage with an index of price triggers by level,
+//! so evaluation only visits the triggers a price actually affects
+
+use crate::commands::{
+    price,
+    triggers::{Target, Trigger},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::RangeBounds;
+
+type Levels<K> = BTreeMap<price::Price, Vec<K>>;
+
+/// Chats with price triggers at each level, split by direction and whether the trigger is armed
+struct Index<K> {
+    armed_lower: Levels<K>,
+    armed_higher: Levels<K>,
+    unarmed_lower: Levels<K>,
+    unarmed_higher: Levels<K>,
+    /// Number of portfolio triggers per chat, these depend on positions and are not indexed by level
+    portfolio: HashMap<K, usize>,
+}
+
+impl<K> Default for Index<K> {
+    fn default() -> Self {
+        Self {
+            armed_lower: BTreeMap::new(),
+            armed_higher: BTreeMap::new(),
+            unarmed_lower: BTreeMap::new(),
+            unarmed_higher: BTreeMap::new(),
+            portfolio: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> Index<K> {
+    fn levels(&mut self, trigger: &Trigger) -> Option<&mut Levels<K>> {
+        match (&trigger.target, trigger.armed) {
+            (Target::Lower(_), true) => Some(&mut self.armed_lower),
+            (Target::Higher(_), true) => Some(&mut self.armed_higher),
+            (Target::Lower(_), false) => Some(&mut self.unarmed_lower),
+            (Target::Higher(_), false) => Some(&mut self.unarmed_higher),
+            _ => None,
+        }
+    }
+
+    fn add(&mut self, chat_id: K, trigger: &Trigger) {
+        let level = trigger.target.price();
+
+        match self.levels(trigger) {
+            Some(levels) => levels.entry(level).or_default().push(chat_id),
+            None => *self.portfolio.entry(chat_id).or_default() += 1,
+        }
+    }
+
+    fn remove(&mut self, chat_id: K, trigger: &Trigger) {
+        let level = trigger.target.price();
+
+        match self.levels(trigger) {
+            Some(levels) => {
+                if let Some(chat_ids) = levels.get_mut(&level) {
+                    if let Some(position) = chat_ids.iter().position(|x| *x == chat_id) {
+                        chat_ids.swap_remove(position);
+                    }
+
+                    if chat_ids.is_empty() {
+                        levels.remove(&level);
+                    }
+                }
+            }
+            None => {
+                if let Some(count) = self.portfolio.get_mut(&chat_id) {
+                    *count -= 1;
+
+                    if *count == 0 {
+                        self.portfolio.remove(&chat_id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Triggers of every chat, kept sorted within a chat
+pub struct TriggerStore<K> {
+    chats: HashMap<K, Vec<Trigger>>,
+    index: Index<K>,
+    /// Chats the bot can't message, their triggers are kept but not evaluated
+    disabled: HashSet<K>,
+}
+
+impl<K> Default for TriggerStore<K> {
+    fn default() -> Self {
+        Self {
+            chats: HashMap::new(),
+            index: Index::default(),
+            disabled: HashSet::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> TriggerStore<K> {
+    pub fn get(&self, chat_id: &K) -> Option<&Vec<Trigger>> {
+        self.chats.get(chat_id)
+    }
+
+    pub fn contains_key(&self, chat_id: &K) -> bool {
+        self.chats.contains_key(chat_id)
+    }
+
+    /// Number of chats with triggers
+    pub fn len(&self) -> usize {
+        self.chats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chats.is_empty()
+    }
+
+    /// Number of triggers of all chats
+    pub fn trigger_count(&self) -> usize {
+        self.chats.values().map(Vec::len).sum()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.chats.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Vec<Trigger>> {
+        self.chats.values()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &Vec<Trigger>)> {
+        self.chats.iter()
+    }
+
+    /// Inserts `trigger` keeping chat's triggers sorted, returns `false` if a trigger with the same target already exists
+    pub fn insert(&mut self, chat_id: K, trigger: Trigger) -> bool {
+        let chat_triggers = self.chats.entry(chat_id).or_default();
+
+        if chat_triggers.iter().any(|x| x.target == trigger.target) {
+            return false;
+        }
+
+        self.index.add(chat_id, &trigger);
+
+        chat_triggers.push(trigger);
+        chat_triggers.sort_by(|a, b| {
+            a.target
+                .partial_cmp(&b.target)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        true
+    }
+
+    /// Removes the trigger with the given target, dropping the chat if it has no triggers left
+    pub fn remove(&mut self, chat_id: K, target: &Target) -> bool {
+        let Some(chat_triggers) = self.chats.get_mut(&chat_id) else {
+            return false;
+        };
+
+        let length_before = chat_triggers.len();
+        chat_triggers.retain(|trigger| {
+            let is_removed = trigger.target.is_same(target);
+
+            if is_removed {
+                self.index.remove(chat_id, trigger);
+            }
+
+            !is_removed
+        });
+        let found = length_before != chat_triggers.len();
+
+        if chat_triggers.is_empty() {
+            self.chats.remove(&chat_id);
+        }
+
+        found
+    }
+
+    /// Removes all triggers of the chat
+    pub fn remove_chat(&mut self, chat_id: &K) -> Option<Vec<Trigger>> {
+        let chat_triggers = self.chats.remove(chat_id)?;
+
+        for trigger in &chat_triggers {
+            self.index.remove(*chat_id, trigger);
+        }
+
+        Some(chat_triggers)
+    }
+
+    pub fn is_disabled(&self, chat_id: &K) -> bool {
+        self.disabled.contains(chat_id)
+    }
+
+    /// Stops evaluating the chat's triggers until it's enabled again
+    pub fn disable(&mut self, chat_id: K) {
+        self.disabled.insert(chat_id);
+    }
+
+    /// Returns `false` if the chat wasn't disabled
+    pub fn enable(&mut self, chat_id: &K) -> bool {
+        self.disabled.remove(chat_id)
+    }
+
+    /// Arms the trigger with the given target, so it fires the next time its threshold is reached
+    pub fn arm(&mut self, chat_id: K, target: &Target) {
+        let Some(chat_triggers) = self.chats.get_mut(&chat_id) else {
+            return;
+        };
+
+        for trigger in chat_triggers
+            .iter_mut()
+            .filter(|x| !x.armed && x.target.is_same(target))
+        {
+            self.index.remove(chat_id, trigger);
+            trigger.armed = true;
+            self.index.add(chat_id, trigger);
+        }
+    }
+
+    /// Price triggers whose levels fall into `range`, `lower` and `armed` select the part of the index
+    pub fn price_triggers(
+        &self,
+        lower: bool,
+        armed: bool,
+        range: impl RangeBounds<price::Price>,
+    ) -> impl Iterator<Item = (K, &Trigger)> {
+        let levels = match (lower, armed) {
+            (true, true) => &self.index.armed_lower,
+            (false, true) => &self.index.armed_higher,
+            (true, false) => &self.index.unarmed_lower,
+            (false, false) => &self.index.unarmed_higher,
+        };
+
+        levels.range(range).flat_map(move |(level, chat_ids)| {
+            chat_ids.iter().flat_map(move |chat_id| {
+                self.chats
+                    .get(chat_id)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |x| {
+                        x.armed == armed
+                            && x.target.is_lower() == lower
+                            && matches!(x.target, Target::Lower(_) | Target::Higher(_))
+                            && x.target.price() == *level
+                    })
+                    .map(move |x| (*chat_id, x))
+            })
+        })
+    }
+
+    /// Triggers of chats which have any portfolio triggers
+    pub fn portfolio_triggers(&self) -> impl Iterator<Item = (K, &Trigger)> {
+        self.index.portfolio.keys().flat_map(move |chat_id| {
+            self.chats
+                .get(chat_id)
+                .into_iter()
+                .flatten()
+                .filter(|x| !matches!(x.target, Target::Lower(_) | Target::Higher(_)))
+                .map(move |x| (*chat_id, x))
+        })
+    }
+}
+
+impl<K: Copy + Eq + Hash> FromIterator<(K, Vec<Trigger>)> for TriggerStore<K> {
+    fn from_iter<I: IntoIterator<Item = (K, Vec<Trigger>)>>(chats: I) -> Self {
+        let mut store = Self::default();
+
+        for (chat_id, triggers) in chats {
+            for trigger in triggers {
+                store.insert(chat_id, trigger);
+            }
+        }
+
+        store
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for TriggerStore<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(&self.chats).finish()
+    }
+}
+
+impl<K: Copy + Eq + Hash> std::ops::Index<&K> for TriggerStore<K> {
+    type Output = Vec<Trigger>;
+
+    fn index(&self, chat_id: &K) -> &Self::Output {
+        &self.chats[chat_id]
+    }
+}
+
+/// Stored as the map of chats and the disabled chats, the index is rebuilt on restore
+impl<K: Serialize> Serialize for TriggerStore<K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.chats, &self.disabled).serialize(serializer)
+    }
+}
+
+impl<'de, K: Copy + Eq + Hash + Deserialize<'de>> Deserialize<'de> for TriggerStore<K> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (chats, disabled) =
+            <(HashMap<K, Vec<Trigger>>, HashSet<K>)>::deserialize(deserializer)?;
+
+        Ok(Self {
+            disabled,
+            ..chats.into_iter().collect()
+        })
+    }
+}
@@ -1,12 +1,28 @@
-use crate::commands::{price, HandlerResult, MyDialogue, State};
+use crate::commands::{
+    alerts,
+    callback::{Callback, TriggerKind},
+    engine,
+    feed::FeedMonitor,
+    history::{self, History},
+    notifier::{Delivery, Notifier},
+    portfolio::Portfolio,
+    price,
+    settings::{self, Language, QuietMode, WrongSide},
+    storage::{Backup, Storage},
+    store::TriggerStore,
+    Deps, HandlerResult, MyDialogue, State,
+};
+use crate::metrics::{Metrics, METRICS};
 
 use anyhow::{Context, Result};
 use log::{error, info, warn};
 
-use bincode::{deserialize, serialize};
+use bincode::deserialize;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::fs::{read, write};
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -16,60 +32,406 @@ use tokio::time::{interval, Duration};
 use teloxide::{
     prelude::*,
     types::{InlineKeyboardButton, InlineKeyboardMarkup},
+    RequestError,
 };
 
+const PROCESS_INTERVAL: Duration = Duration::from_millis(200);
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_EXPIRY_DAYS: i64 = 365;
+pub const MAX_LABEL_LENGTH: usize = 64;
+/// Format version of triggers.bak
+pub const BACKUP_VERSION: u16 = 1;
+
 #[derive(PartialOrd, PartialEq, Clone, Serialize, Deserialize)]
-pub enum Trigger {
+pub enum Target {
     Lower(price::Price),
     Higher(price::Price),
-    Neutral(price::Price),
+    /// Portfolio value in USD
+    ValueLower(Decimal),
+    ValueHigher(Decimal),
+    /// Unrealized portfolio P&L in percent
+    PnlLower(Decimal),
+    PnlHigher(Decimal),
 }
 
-impl Trigger {
-    fn set(&mut self, price: price::Price) {
+impl Target {
+    /// Sets the threshold, rounding prices to the exchange tick size and other quantities to cents
+    pub fn set(&mut self, price: price::Price) {
+        let price = match self {
+            Self::Lower(_) | Self::Higher(_) => price::to_tick(price),
+            _ => price.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero),
+        };
+
         match self {
-            Self::Lower(x) | Self::Higher(x) | Self::Neutral(x) => *x = price,
+            Self::Lower(x)
+            | Self::Higher(x)
+            | Self::ValueLower(x)
+            | Self::ValueHigher(x)
+            | Self::PnlLower(x)
+            | Self::PnlHigher(x) => *x = price,
         }
     }
 
-    const fn price(&self) -> price::Price {
+    /// Whether a price or portfolio value threshold is at least one tick or one cent,
+    /// P&L thresholds may be negative
+    pub fn is_valid(&self) -> bool {
         match self {
-            Self::Lower(x) | Self::Higher(x) | Self::Neutral(x) => *x,
+            Self::Lower(x) | Self::Higher(x) => *x >= price::TICK_SIZE,
+            Self::ValueLower(x) | Self::ValueHigher(x) => *x >= Decimal::new(1, 2),
+            Self::PnlLower(_) | Self::PnlHigher(_) => true,
         }
     }
-}
 
-impl fmt::Display for Trigger {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    /// Threshold of the trigger, in the units of the watched quantity
+    pub const fn price(&self) -> price::Price {
         match self {
-            Self::Lower(x) => write!(f, "менше ніж {x:.2}$"),
-            Self::Higher(x) => write!(f, "більше ніж {x:.2}$"),
-            Self::Neutral(_) => unreachable!(),
+            Self::Lower(x)
+            | Self::Higher(x)
+            | Self::ValueLower(x)
+            | Self::ValueHigher(x)
+            | Self::PnlLower(x)
+            | Self::PnlHigher(x) => *x,
+        }
+    }
+
+    pub const fn is_lower(&self) -> bool {
+        matches!(
+            self,
+            Self::Lower(_) | Self::ValueLower(_) | Self::PnlLower(_)
+        )
+    }
+
+    /// Target with the same level in the opposite direction
+    pub const fn opposite(&self) -> Self {
+        match *self {
+            Self::Lower(x) => Self::Higher(x),
+            Self::Higher(x) => Self::Lower(x),
+            Self::ValueLower(x) => Self::ValueHigher(x),
+            Self::ValueHigher(x) => Self::ValueLower(x),
+            Self::PnlLower(x) => Self::PnlHigher(x),
+            Self::PnlHigher(x) => Self::PnlLower(x),
+        }
+    }
+
+    /// Whether both targets are of the same kind and level
+    pub fn is_same(&self, other: &Self) -> bool {
+        TriggerKind::from(self) == TriggerKind::from(other) && self.price() == other.price()
+    }
+
+    /// Condition of the target as shown to a chat, e.g. `більше ніж 7.50$`
+    pub fn describe(&self, language: Language) -> String {
+        let direction = if self.is_lower() {
+            language.pick("менше ніж", "below")
+        } else {
+            language.pick("більше ніж", "above")
+        };
+
+        match self {
+            Self::Lower(x) | Self::Higher(x) => format!("{direction} {}$", price::display(*x)),
+            Self::ValueLower(x) | Self::ValueHigher(x) => format!(
+                "{} {direction} {x:.2}$",
+                language.pick("вартість портфеля", "portfolio value")
+            ),
+            Self::PnlLower(x) | Self::PnlHigher(x) => format!(
+                "{} {direction} {x:+.2}%",
+                language.pick("P&L портфеля", "portfolio P&L")
+            ),
         }
     }
 }
 
-impl fmt::Debug for Trigger {
+impl fmt::Debug for Target {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Lower(x) => write!(f, "Trigger::Lower({x:.2})"),
-            Self::Higher(x) => write!(f, "Trigger::Higher({x:.2})"),
-            Self::Neutral(_) => unreachable!(),
+            Self::Lower(x) => write!(f, "Target::Lower({})", price::display(*x)),
+            Self::Higher(x) => write!(f, "Target::Higher({})", price::display(*x)),
+            Self::ValueLower(x) => write!(f, "Target::ValueLower({x:.2})"),
+            Self::ValueHigher(x) => write!(f, "Target::ValueHigher({x:.2})"),
+            Self::PnlLower(x) => write!(f, "Target::PnlLower({x:.2})"),
+            Self::PnlHigher(x) => write!(f, "Target::PnlHigher({x:.2})"),
         }
     }
 }
 
-pub async fn start(bot: Bot, dialogue: MyDialogue) -> HandlerResult {
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trigger {
+    pub target: Target,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Triggers created on the reached side of their threshold wait for the price to cross back first
+    pub armed: bool,
+    /// Reminder of why the trigger was set
+    pub label: Option<String>,
+    /// The trigger doesn't fire or arm before this time
+    pub snoozed_until: Option<DateTime<Utc>>,
+}
+
+/// Trigger as stored by the first release, before backups had a header
+#[derive(Deserialize)]
+enum BaselineTrigger {
+    Lower(f64),
+    Higher(f64),
+    /// Only used to look up triggers to delete, never stored
+    #[allow(dead_code)]
+    Neutral(f64),
+}
+
+impl BaselineTrigger {
+    fn into_trigger(self) -> Option<Trigger> {
+        let (kind, price) = match self {
+            Self::Lower(x) => (TriggerKind::Lower, x),
+            Self::Higher(x) => (TriggerKind::Higher, x),
+            Self::Neutral(_) => return None,
+        };
+
+        Some(Trigger::new(kind.target(Decimal::try_from(price).ok()?)))
+    }
+}
+
+impl Trigger {
+    pub const fn new(target: Target) -> Self {
+        Self {
+            target,
+            expires_at: None,
+            armed: true,
+            label: None,
+            snoozed_until: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        self.snoozed_until
+            .is_some_and(|snoozed_until| now < snoozed_until)
+    }
+}
+
+impl Trigger {
+    /// Condition with the remaining time, the snooze and the label of the trigger
+    pub fn describe(&self, language: Language) -> String {
+        let now = Utc::now();
+        let mut description = self.target.describe(language);
+
+        if let Some(expires_at) = self.expires_at {
+            description.push_str(&format!(
+                " ({})",
+                format_remaining(expires_at - now, language)
+            ));
+        }
+
+        if let Some(snoozed_until) = self.snoozed_until.filter(|x| *x > now) {
+            description.push_str(&format!(
+                " ({}, {})",
+                language.pick("відкладено", "snoozed"),
+                format_remaining(snoozed_until - now, language)
+            ));
+        }
+
+        if let Some(label) = &self.label {
+            description.push_str(&format!(" — «{label}»"));
+        }
+
+        description
+    }
+}
+
+fn format_remaining(remaining: chrono::Duration, language: Language) -> String {
+    let minutes = remaining.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+
+    match language {
+        Language::Ukrainian if days > 0 => format!("ще {days} д {hours} год"),
+        Language::Ukrainian if hours > 0 => format!("ще {hours} год {minutes} хв"),
+        Language::Ukrainian => format!("ще {minutes} хв"),
+        Language::English if days > 0 => format!("{days} d {hours} h left"),
+        Language::English if hours > 0 => format!("{hours} h {minutes} min left"),
+        Language::English => format!("{minutes} min left"),
+    }
+}
+
+fn no_triggers(language: Language) -> &'static str {
+    language.pick("У вас наразі немає тригерів", "You have no triggers yet")
+}
+
+/// Reply to a message or button which doesn't belong to the current step
+fn choose_option(language: Language) -> &'static str {
+    language.pick(
+        "Оберіть одну з доступних опцій",
+        "Choose one of the available options",
+    )
+}
+
+/// Accepts either a relative duration (`30m`, `24h`, `7d`) or a date (`2026-12-01`),
+/// optionally prefixed with `for` or `until`. Dates mean midnight in the chat's `time_zone`,
+/// either form is refused past [`MAX_EXPIRY_DAYS`]
+pub fn parse_expiry(text: &str, now: DateTime<Utc>, time_zone: Tz) -> Option<DateTime<Utc>> {
+    let text = text.trim().to_lowercase();
+    let text = text
+        .strip_prefix("until ")
+        .or_else(|| text.strip_prefix("for "))
+        .unwrap_or(&text)
+        .trim();
+
+    let expires_at = if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        // Where daylight saving time starts at midnight, the day starts an hour later
+        let midnight = date.and_hms_opt(0, 0, 0)?;
+        time_zone
+            .from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| {
+                time_zone
+                    .from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+                    .earliest()
+            })?
+            .with_timezone(&Utc)
+    } else {
+        let unit = text.chars().last()?;
+        let amount = text[..text.len() - unit.len_utf8()]
+            .trim()
+            .parse::<i64>()
+            .ok()?;
+
+        let minutes = match unit {
+            'm' => Some(amount),
+            'h' => amount.checked_mul(60),
+            'd' => amount.checked_mul(24 * 60),
+            _ => None,
+        }?;
+
+        if minutes > MAX_EXPIRY_DAYS * 24 * 60 {
+            return None;
+        }
+
+        now + chrono::Duration::minutes(minutes)
+    };
+
+    (expires_at > now && expires_at <= now + chrono::Duration::days(MAX_EXPIRY_DAYS))
+        .then_some(expires_at)
+}
+
+/// Trims the label, `None` if it's empty or too long
+pub fn parse_label(text: &str) -> Option<String> {
+    let label = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    (!label.is_empty() && label.chars().count() <= MAX_LABEL_LENGTH).then_some(label)
+}
+
+/// Parses `> 7.5 24h`, `value < 1000`, `pnl > 20% 7d` or `> 7.5 take profit`
+fn parse_arguments(args: &str, now: DateTime<Utc>, time_zone: Tz) -> Option<Trigger> {
+    let args = args.trim();
+
+    let (kinds, args) = if let Some(rest) = args.strip_prefix("value") {
+        ((TriggerKind::ValueLower, TriggerKind::ValueHigher), rest)
+    } else if let Some(rest) = args.strip_prefix("pnl") {
+        ((TriggerKind::PnlLower, TriggerKind::PnlHigher), rest)
+    } else {
+        ((TriggerKind::Lower, TriggerKind::Higher), args)
+    };
+    let args = args.trim();
+
+    let (kind, rest) = if let Some(rest) = args.strip_prefix('<') {
+        (kinds.0, rest)
+    } else {
+        (kinds.1, args.strip_prefix('>')?)
+    };
+
+    let (price, rest) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim(), ""));
+
+    let price = price::parse(price.trim_end_matches(['%', '$']))?;
+
+    // The expiry is optional, everything after it is the label
+    let (expiry, label) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim(), ""));
+
+    let (expires_at, label) = match parse_expiry(expiry, now, time_zone) {
+        Some(expires_at) => (Some(expires_at), label),
+        None => (None, rest),
+    };
+
+    let label = if label.trim().is_empty() {
+        None
+    } else {
+        Some(parse_label(label)?)
+    };
+
+    let target = kind.target(price);
+
+    if !target.is_valid() {
+        return None;
+    }
+
+    Some(Trigger {
+        expires_at,
+        label,
+        ..Trigger::new(target)
+    })
+}
+
+pub async fn start(
+    bot: Bot,
+    dialogue: MyDialogue,
+    args: String,
+    deps: Deps,
+    language: Language,
+) -> HandlerResult {
+    if !args.trim().is_empty() {
+        info!("Adding trigger from command arguments...");
+
+        let time_zone = deps
+            .settings
+            .lock()
+            .await
+            .get(&dialogue.chat_id())
+            .map_or(Tz::UTC, |x| x.time_zone);
+
+        match parse_arguments(&args, Utc::now(), time_zone) {
+            Some(trigger) => {
+                add(bot, &dialogue, trigger, &deps, language).await?;
+            }
+            None => {
+                warn!("User provided invalid trigger arguments: {args:?}");
+
+                bot.send_message(
+                    dialogue.chat_id(),
+                    language.pick(
+                        "Формат: /addtrigger [value | pnl] > 7.5 [24h | 7d | 2026-12-01] [підпис]",
+                        "Usage: /addtrigger [value | pnl] > 7.5 [24h | 7d | 2026-12-01] [label]",
+                    ),
+                )
+                .await
+                .context("Failed to send Telegram message")?;
+            }
+        }
+
+        return Ok(());
+    }
+
     let buttons = [
-        ("Ціна більше ніж ...", "Higher"),
-        ("Ціна менше ніж ...", "Lower"),
+        (
+            language.pick("Ціна більше ніж ...", "Price above ..."),
+            TriggerKind::Higher,
+        ),
+        (
+            language.pick("Ціна менше ніж ...", "Price below ..."),
+            TriggerKind::Lower,
+        ),
     ]
-    .map(|(button, callback)| [InlineKeyboardButton::callback(button, callback)]);
+    .map(|(button, kind)| [Callback::TriggerType(kind).button(button)]);
 
-    bot.send_message(dialogue.chat_id(), "Оберіть тип тригера:")
-        .reply_markup(InlineKeyboardMarkup::new(buttons))
-        .await
-        .context("Failed to send Telegram message")?;
+    bot.send_message(
+        dialogue.chat_id(),
+        language.pick("Оберіть тип тригера:", "Choose the trigger type:"),
+    )
+    .reply_markup(InlineKeyboardMarkup::new(buttons))
+    .await
+    .context("Failed to send Telegram message")?;
 
     dialogue
         .update(State::ReceiveTriggerType)
@@ -83,36 +445,28 @@ pub async fn receive_trigger_type(
     bot: Bot,
     dialogue: MyDialogue,
     q: CallbackQuery,
+    language: Language,
 ) -> HandlerResult {
     info!("Receiving trigger type...");
 
-    match q.data.as_deref() {
-        Some("Lower") => {
-            bot.send_message(dialogue.chat_id(), "Вкажіть ціну:")
-                .await
-                .context("Failed to send Telegram message")?;
-
-            dialogue
-                .update(State::ReceivePrice {
-                    trigger: Trigger::Lower(0.0),
-                })
-                .await
-                .context("Failed to update state")?;
-        }
-        Some("Higher") => {
-            bot.send_message(dialogue.chat_id(), "Вкажіть ціну:")
-                .await
-                .context("Failed to send Telegram message")?;
+    match Callback::from_query(&q) {
+        Some(Callback::TriggerType(kind)) => {
+            bot.send_message(
+                dialogue.chat_id(),
+                language.pick("Вкажіть ціну:", "Enter the price:"),
+            )
+            .await
+            .context("Failed to send Telegram message")?;
 
             dialogue
                 .update(State::ReceivePrice {
-                    trigger: Trigger::Higher(0.0),
+                    target: kind.target(Decimal::ZERO),
                 })
                 .await
                 .context("Failed to update state")?;
         }
-        Some(_) | None => {
-            bot.send_message(dialogue.chat_id(), "Оберіть одну з доступних опцій")
+        _ => {
+            bot.send_message(dialogue.chat_id(), choose_option(language))
                 .await
                 .context("Failed to send Telegram message")?;
         }
@@ -125,19 +479,190 @@ pub async fn receive_price(
     bot: Bot,
     dialogue: MyDialogue,
     msg: Message,
-    mut trigger: Trigger,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
+    mut target: Target,
+    language: Language,
 ) -> HandlerResult {
     info!("Receiving trigger price...");
 
-    if let Some(Ok(price)) = msg.text().map(|x| x.replace(',', ".").parse::<f64>()) {
-        trigger.set(price);
-        add(bot, trigger, msg.chat.id, triggers).await?;
+    let target = msg
+        .text()
+        .and_then(price::parse)
+        .map(|price| {
+            target.set(price);
+            target
+        })
+        .filter(Target::is_valid);
 
-        dialogue.exit().await.context("Failed to reset state")?;
+    if let Some(target) = target {
+        bot.send_message(
+            msg.chat.id,
+            language.pick(
+                "Вкажіть термін дії тригера (наприклад 24h, 7d або 2026-12-01):",
+                "Enter how long the trigger lasts (e.g. 24h, 7d or 2026-12-01):",
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new([[
+            Callback::NoExpiry.button(language.pick("Без терміну", "No expiry"))
+        ]]))
+        .await
+        .context("Failed to send Telegram message")?;
+
+        dialogue
+            .update(State::ReceiveExpiry { target })
+            .await
+            .context("Failed to update state")?;
     } else {
         warn!("User provided invalid price: {:?}", msg.text());
-        bot.send_message(msg.chat.id, "Вкажіть число:")
+        bot.send_message(
+            msg.chat.id,
+            language.pick("Вкажіть коректне число:", "Enter a valid number:"),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+    }
+
+    Ok(())
+}
+
+async fn ask_label(
+    bot: Bot,
+    dialogue: MyDialogue,
+    trigger: Trigger,
+    language: Language,
+) -> HandlerResult {
+    bot.send_message(
+        dialogue.chat_id(),
+        language.pick(
+            "Додайте підпис, щоб не забути навіщо цей тригер (наприклад «фіксація прибутку»):",
+            "Add a label to remember what the trigger is for (e.g. «take profit»):",
+        ),
+    )
+    .reply_markup(InlineKeyboardMarkup::new([[
+        Callback::NoLabel.button(language.pick("Без підпису", "No label"))
+    ]]))
+    .await
+    .context("Failed to send Telegram message")?;
+
+    dialogue
+        .update(State::ReceiveLabel { trigger })
+        .await
+        .context("Failed to update state")?;
+
+    Ok(())
+}
+
+pub async fn receive_expiry(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    target: Target,
+    deps: Deps,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving trigger expiry...");
+
+    let time_zone = deps
+        .settings
+        .lock()
+        .await
+        .get(&msg.chat.id)
+        .map_or(Tz::UTC, |x| x.time_zone);
+
+    if let Some(expires_at) = msg
+        .text()
+        .and_then(|x| parse_expiry(x, Utc::now(), time_zone))
+    {
+        let trigger = Trigger {
+            expires_at: Some(expires_at),
+            ..Trigger::new(target)
+        };
+        ask_label(bot, dialogue, trigger, language).await?;
+    } else {
+        warn!("User provided invalid expiry: {:?}", msg.text());
+        bot.send_message(
+            msg.chat.id,
+            language.pick(
+                "Вкажіть термін у форматі 24h, 7d або 2026-12-01:",
+                "Enter the expiry as 24h, 7d or 2026-12-01:",
+            ),
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+    }
+
+    Ok(())
+}
+
+pub async fn skip_expiry(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    target: Target,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving trigger without expiry...");
+
+    if let Some(Callback::NoExpiry) = Callback::from_query(&q) {
+        ask_label(bot, dialogue, Trigger::new(target), language).await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), choose_option(language))
+            .await
+            .context("Failed to send Telegram message")?;
+    }
+
+    Ok(())
+}
+
+pub async fn receive_label(
+    bot: Bot,
+    dialogue: MyDialogue,
+    msg: Message,
+    trigger: Trigger,
+    deps: Deps,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving trigger label...");
+
+    if let Some(label) = msg.text().and_then(parse_label) {
+        let trigger = Trigger {
+            label: Some(label),
+            ..trigger
+        };
+        dialogue.exit().await.context("Failed to reset state")?;
+        add(bot, &dialogue, trigger, &deps, language).await?;
+    } else {
+        warn!("User provided invalid label: {:?}", msg.text());
+        bot.send_message(
+            msg.chat.id,
+            match language {
+                Language::Ukrainian => format!("Вкажіть підпис до {MAX_LABEL_LENGTH} символів:"),
+                Language::English => {
+                    format!("Enter a label of up to {MAX_LABEL_LENGTH} characters:")
+                }
+            },
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+    }
+
+    Ok(())
+}
+
+pub async fn skip_label(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    trigger: Trigger,
+    deps: Deps,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving trigger without label...");
+
+    if let Some(Callback::NoLabel) = Callback::from_query(&q) {
+        dialogue.exit().await.context("Failed to reset state")?;
+        add(bot, &dialogue, trigger, &deps, language).await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), choose_option(language))
             .await
             .context("Failed to send Telegram message")?;
     }
@@ -145,48 +670,280 @@ pub async fn receive_price(
     Ok(())
 }
 
+/// How a new trigger is added according to the wrong side policy of the chat
+pub enum Arming {
+    /// Unarmed triggers wait for the engine to arm them on the first tick their threshold isn't reached
+    Insert(Trigger),
+    /// The threshold is already reached and the chat rejects such triggers
+    Reject(Decimal),
+    /// The threshold is already reached, so the trigger in the opposite direction is proposed instead
+    Convert(Trigger, Decimal),
+}
+
+/// Current value of the quantity watched by `target`, `None` without a fresh quote or positions
+async fn observe(deps: &Deps, chat_id: ChatId, target: &Target) -> Option<Decimal> {
+    let quote = deps
+        .quotes
+        .quote(price::QUOTE_MAX_AGE)
+        .await
+        .ok()
+        .filter(|x| !x.is_stale())?;
+
+    let locked_portfolios = deps.portfolios.lock().await;
+    engine::observe(target, quote.price, locked_portfolios.get(&chat_id))
+}
+
+/// Applies the `wrong_side` policy to a new trigger, `observed` is the current value of the quantity it watches
+pub fn apply_wrong_side(
+    mut trigger: Trigger,
+    wrong_side: WrongSide,
+    observed: Option<Decimal>,
+) -> Arming {
+    // A snoozed trigger just fired, it fires again if the threshold is still reached once the snooze is over
+    if !trigger.armed || trigger.snoozed_until.is_some() {
+        return Arming::Insert(trigger);
+    }
+
+    let observed = match wrong_side {
+        WrongSide::ArmOnCrossing => None,
+        WrongSide::Reject | WrongSide::Convert => observed,
+    };
+
+    let Some(observed) = observed else {
+        trigger.armed = false;
+        return Arming::Insert(trigger);
+    };
+
+    if !engine::is_reached(&trigger.target, observed) {
+        return Arming::Insert(trigger);
+    }
+
+    match wrong_side {
+        WrongSide::Reject => Arming::Reject(observed),
+        WrongSide::Convert | WrongSide::ArmOnCrossing => Arming::Convert(
+            Trigger {
+                target: trigger.target.opposite(),
+                ..trigger
+            },
+            observed,
+        ),
+    }
+}
+
+/// Applies the wrong side policy of the chat to a new trigger
+pub async fn arming(trigger: Trigger, chat_id: ChatId, deps: &Deps) -> Arming {
+    let wrong_side = deps
+        .settings
+        .lock()
+        .await
+        .get(&chat_id)
+        .map(|x| x.wrong_side)
+        .unwrap_or_default();
+
+    let observed = match wrong_side {
+        WrongSide::ArmOnCrossing => None,
+        WrongSide::Reject | WrongSide::Convert => observe(deps, chat_id, &trigger.target).await,
+    };
+
+    let target = trigger.target.clone();
+    let arming = apply_wrong_side(trigger, wrong_side, observed);
+
+    if !matches!(arming, Arming::Insert(_)) {
+        info!("Trigger {target:?} is already reached for chat {chat_id}, {wrong_side:?}");
+    }
+
+    arming
+}
+
+/// Current value of the quantity watched by `target`, as shown to the chat
+pub fn describe_observed(target: &Target, observed: Decimal, language: Language) -> String {
+    match target {
+        Target::Lower(_) | Target::Higher(_) => format!(
+            "{} {}$",
+            language.pick("Ціна вже", "The price is already"),
+            price::display(observed)
+        ),
+        Target::ValueLower(_) | Target::ValueHigher(_) => format!(
+            "{} {observed:.2}$",
+            language.pick("Вартість портфеля вже", "The portfolio value is already")
+        ),
+        Target::PnlLower(_) | Target::PnlHigher(_) => format!(
+            "{} {observed:+.2}%",
+            language.pick("P&L портфеля вже", "The portfolio P&L is already")
+        ),
+    }
+}
+
 pub async fn add(
     bot: Bot,
+    dialogue: &MyDialogue,
     trigger: Trigger,
-    chat_id: ChatId,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
+    deps: &Deps,
+    language: Language,
 ) -> HandlerResult {
     info!("Adding trigger...");
 
-    let mut locked_triggers = triggers.lock().await;
+    let chat_id = dialogue.chat_id();
+    let trigger_target = trigger.target.clone();
+
+    match arming(trigger, chat_id, deps).await {
+        Arming::Insert(trigger) => insert(bot, chat_id, trigger, deps, language).await?,
+        Arming::Reject(observed) => {
+            let observed = describe_observed(&trigger_target, observed, language);
+            let target = trigger_target.describe(language);
+
+            bot.send_message(
+                chat_id,
+                match language {
+                    Language::Ukrainian => format!(
+                        "{observed}, тому тригер «{target}» спрацював би одразу. \
+                         Тригер не додано, змінити поведінку можна в /settings"
+                    ),
+                    Language::English => format!(
+                        "{observed}, so the trigger «{target}» would fire at once. \
+                         The trigger wasn't added, this can be changed in /settings"
+                    ),
+                },
+            )
+            .await
+            .context("Failed to send Telegram message")?;
+        }
+        Arming::Convert(trigger, observed) => {
+            let keyboard = InlineKeyboardMarkup::new([
+                [Callback::ConfirmConvert.button(language.pick("Додати", "Add"))],
+                [Callback::CancelConvert.button(language.pick("Скасувати", "Cancel"))],
+            ]);
+            let observed = describe_observed(&trigger.target, observed, language);
+            let (requested, proposed) = (
+                trigger.target.opposite().describe(language),
+                trigger.target.describe(language),
+            );
+
+            bot.send_message(
+                chat_id,
+                match language {
+                    Language::Ukrainian => format!(
+                        "{observed}, тому тригер «{requested}» спрацював би одразу. \
+                         Додати натомість тригер «{proposed}»?"
+                    ),
+                    Language::English => format!(
+                        "{observed}, so the trigger «{requested}» would fire at once. \
+                         Add the trigger «{proposed}» instead?"
+                    ),
+                },
+            )
+            .reply_markup(keyboard)
+            .await
+            .context("Failed to send Telegram message")?;
 
-    if locked_triggers
-        .entry(chat_id)
-        .or_default()
-        .iter()
-        .any(|x| x == &trigger)
-    {
-        info!("Trigger {trigger:?} already exists for chat {chat_id}");
+            dialogue
+                .update(State::ConfirmConvert { trigger })
+                .await
+                .context("Failed to update state")?;
+        }
+    }
+
+    Ok(())
+}
 
-        bot.send_message(chat_id, format!("Тригер `{trigger:?}` вже існує"))
+pub async fn confirm_convert(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    trigger: Trigger,
+    deps: Deps,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving converted trigger confirmation...");
+
+    match Callback::from_query(&q) {
+        Some(Callback::ConfirmConvert) => {
+            dialogue.exit().await.context("Failed to reset state")?;
+            insert(bot, dialogue.chat_id(), trigger, &deps, language).await?;
+        }
+        Some(Callback::CancelConvert) => {
+            dialogue.exit().await.context("Failed to reset state")?;
+
+            bot.send_message(
+                dialogue.chat_id(),
+                language.pick("Тригер не додано", "The trigger wasn't added"),
+            )
             .await
             .context("Failed to send Telegram message")?;
+        }
+        _ => {
+            bot.send_message(dialogue.chat_id(), choose_option(language))
+                .await
+                .context("Failed to send Telegram message")?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn insert(
+    bot: Bot,
+    chat_id: ChatId,
+    trigger: Trigger,
+    deps: &Deps,
+    language: Language,
+) -> HandlerResult {
+    let mut locked_triggers = deps.triggers.lock().await;
+
+    if let Err(exceeded) = deps.limits.check(&locked_triggers, chat_id).await {
+        info!("Trigger {trigger:?} rejected for chat {chat_id}: {exceeded:?}");
+
+        let exceeded = exceeded.describe(language);
+
+        bot.send_message(
+            chat_id,
+            match language {
+                Language::Ukrainian => format!(
+                    "Тригер не додано: {exceeded}. \
+                     Видалити непотрібні тригери можна через /deletetrigger"
+                ),
+                Language::English => format!(
+                    "The trigger wasn't added: {exceeded}. \
+                     Triggers you don't need can be deleted with /deletetrigger"
+                ),
+            },
+        )
+        .await
+        .context("Failed to send Telegram message")?;
 
         return Ok(());
     }
 
-    locked_triggers
-        .entry(chat_id)
-        .or_default()
-        .push(trigger.clone());
+    if !locked_triggers.insert(chat_id, trigger.clone()) {
+        info!("Trigger {trigger:?} already exists for chat {chat_id}");
 
-    locked_triggers
-        .entry(chat_id)
-        .or_default()
-        .sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        bot.send_message(
+            chat_id,
+            match language {
+                Language::Ukrainian => format!("Тригер `{trigger:?}` вже існує"),
+                Language::English => format!("Trigger `{trigger:?}` already exists"),
+            },
+        )
+        .await
+        .context("Failed to send Telegram message")?;
+
+        return Ok(());
+    }
 
-    if let Err(err) = backup(&locked_triggers) {
+    if let Err(err) = backup(&deps.storage, &locked_triggers) {
         error!("Failed to backup triggers, due to: {}", err);
     }
 
     bot.send_message(
         chat_id,
-        format!("Вам прийде повідомлення якщо ціна буде {trigger}"),
+        format!(
+            "{} {}",
+            language.pick(
+                "Вам прийде повідомлення якщо ціна буде",
+                "You will be notified when the price is"
+            ),
+            trigger.describe(language)
+        ),
     )
     .await
     .context("Failed to send Telegram message")?;
@@ -199,26 +956,28 @@ pub async fn add(
 pub async fn list(
     bot: Bot,
     msg: Message,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    language: Language,
 ) -> HandlerResult {
     info!("Listing triggers...");
 
-    let mut locked_triggers = triggers.lock().await;
+    let locked_triggers = triggers.lock().await;
 
     let mut message = if locked_triggers.is_empty() {
-        String::from("У вас наразі немає тригерів")
+        String::from(no_triggers(language))
     } else {
-        String::from(
+        String::from(language.pick(
             "Надіслати повідомлення 
 якщо ціна буде:\n",
-        )
+            "Notify me when the price is:\n",
+        ))
     };
 
     locked_triggers
-        .entry(msg.chat.id)
-        .or_default()
-        .iter()
-        .for_each(|x| message.push_str(format!("{x}\n").as_str()));
+        .get(&msg.chat.id)
+        .into_iter()
+        .flatten()
+        .for_each(|x| message.push_str(&format!("{}\n", x.describe(language))));
 
     bot.send_message(msg.chat.id, message)
         .await
@@ -230,7 +989,8 @@ pub async fn list(
 pub async fn show_trigger_to_delete(
     bot: Bot,
     dialogue: MyDialogue,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    language: Language,
 ) -> HandlerResult {
     info!("Choosing trigger to delete...");
 
@@ -238,18 +998,17 @@ pub async fn show_trigger_to_delete(
     triggers
         .lock()
         .await
-        .entry(dialogue.chat_id())
-        .or_default()
-        .iter()
+        .get(&dialogue.chat_id())
+        .into_iter()
+        .flatten()
         .for_each(|trigger| {
-            buttons.push(InlineKeyboardButton::callback(
-                trigger.to_string(),
-                trigger.price().to_string(),
-            ));
+            buttons.push(
+                Callback::DeleteTrigger(trigger.target.clone()).button(trigger.describe(language)),
+            );
         });
 
     if buttons.is_empty() {
-        bot.send_message(dialogue.chat_id(), "У вас наразі немає тригерів")
+        bot.send_message(dialogue.chat_id(), no_triggers(language))
             .await
             .context("Failed to send Telegram message")?;
         dialogue.exit().await.context("Failed to reset state")?;
@@ -257,15 +1016,21 @@ pub async fn show_trigger_to_delete(
         return Ok(());
     }
 
-    bot.send_message(dialogue.chat_id(), "Оберіть тригер для видалення:")
-        .reply_markup(InlineKeyboardMarkup::new(
-            buttons
-                .chunks(2)
-                .map(<[InlineKeyboardButton]>::to_vec)
-                .collect::<Vec<_>>(),
-        ))
-        .await
-        .context("Failed to send Telegram message")?;
+    bot.send_message(
+        dialogue.chat_id(),
+        language.pick(
+            "Оберіть тригер для видалення:",
+            "Choose the trigger to delete:",
+        ),
+    )
+    .reply_markup(InlineKeyboardMarkup::new(
+        buttons
+            .chunks(2)
+            .map(<[InlineKeyboardButton]>::to_vec)
+            .collect::<Vec<_>>(),
+    ))
+    .await
+    .context("Failed to send Telegram message")?;
 
     dialogue
         .update(State::DeleteTrigger)
@@ -279,21 +1044,18 @@ pub async fn choose_trigger_to_delete(
     bot: Bot,
     dialogue: MyDialogue,
     q: CallbackQuery,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
+    deps: Deps,
+    language: Language,
 ) -> HandlerResult {
     info!("Receiving trigger to delete...");
 
-    match q
-        .data
-        .as_deref()
-        .map(|x| x.replace(',', ".").parse::<f64>())
-    {
-        Some(Ok(price)) => {
-            delete(bot, dialogue.clone(), price, triggers).await?;
+    match Callback::from_query(&q) {
+        Some(Callback::DeleteTrigger(target)) => {
+            delete(bot, dialogue.clone(), target, &deps, language).await?;
             dialogue.exit().await.context("Failed to reset state")?;
         }
         _ => {
-            bot.send_message(dialogue.chat_id(), "Оберіть одну з доступних опцій")
+            bot.send_message(dialogue.chat_id(), choose_option(language))
                 .await
                 .context("Failed to send Telegram message")?;
         }
@@ -303,42 +1065,19 @@ pub async fn choose_trigger_to_delete(
 }
 
 fn remove_triggered(
-    triggered: Vec<(ChatId, Trigger)>,
-    mut locked_triggers: tokio::sync::MutexGuard<'_, HashMap<ChatId, Vec<Trigger>>>,
+    storage: &Storage,
+    triggered: Vec<(ChatId, Target)>,
+    mut locked_triggers: tokio::sync::MutexGuard<'_, TriggerStore<ChatId>>,
 ) -> bool {
     let mut found = false;
 
-    for (chat_id, trigger) in triggered {
-        info!(
-            "Removing {:.2}$ from triggers for chat {}",
-            trigger.price(),
-            chat_id
-        );
-
-        locked_triggers
-            .entry(chat_id)
-            .and_modify(|target_prices| {
-                let length_before = target_prices.len();
+    for (chat_id, target) in triggered {
+        info!("Removing {target:?} from triggers for chat {chat_id}");
 
-                target_prices.retain(|trigger_price| match (trigger_price, trigger.clone()) {
-                    (Trigger::Lower(x), Trigger::Lower(y))
-                    | (Trigger::Higher(x), Trigger::Higher(y)) => (x - y).abs() > f64::EPSILON,
-                    (_, Trigger::Neutral(y)) => (trigger_price.price() - y).abs() > f64::EPSILON,
-                    _ => true,
-                });
-
-                found |= length_before != target_prices.len();
-            })
-            .or_default();
-
-        if locked_triggers[&chat_id].is_empty() {
-            info!("Removing chat {} from triggers", chat_id);
-
-            locked_triggers.remove(&chat_id);
-        }
+        found |= locked_triggers.remove(chat_id, &target);
     }
 
-    if let Err(err) = backup(&locked_triggers) {
+    if let Err(err) = backup(storage, &locked_triggers) {
         error!("Failed to backup triggers, due to: {}", err);
     }
 
@@ -348,20 +1087,27 @@ fn remove_triggered(
 pub async fn delete(
     bot: Bot,
     dialogue: MyDialogue,
-    price: price::Price,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
+    target: Target,
+    deps: &Deps,
+    language: Language,
 ) -> HandlerResult {
     info!("Deleting trigger...");
 
+    let price = price::display(target.price());
+
     if remove_triggered(
-        vec![(dialogue.chat_id(), Trigger::Neutral(price))],
-        triggers.lock().await,
+        &deps.storage,
+        vec![(dialogue.chat_id(), target)],
+        deps.triggers.lock().await,
     ) {
         info!("Deleted trigger for chat {}", dialogue.chat_id());
 
         bot.send_message(
             dialogue.chat_id(),
-            format!("Тригер на {price:.2}$ був видалений"),
+            match language {
+                Language::Ukrainian => format!("Тригер на {price}$ був видалений"),
+                Language::English => format!("The trigger at {price}$ was deleted"),
+            },
         )
         .await
         .context("Failed to send Telegram message")?;
@@ -373,7 +1119,10 @@ pub async fn delete(
 
         bot.send_message(
             dialogue.chat_id(),
-            format!("Тригер {price:.2}$ не був знайдений"),
+            match language {
+                Language::Ukrainian => format!("Тригер {price}$ не був знайдений"),
+                Language::English => format!("The trigger at {price}$ wasn't found"),
+            },
         )
         .await
         .context("Failed to send Telegram message")?;
@@ -382,18 +1131,14 @@ pub async fn delete(
     Ok(())
 }
 
-pub async fn delete_all(
-    bot: Bot,
-    msg: Message,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
-) -> HandlerResult {
+pub async fn delete_all(bot: Bot, msg: Message, deps: Deps, language: Language) -> HandlerResult {
     info!("Deleting all triggers...");
 
-    let mut locked_triggers = triggers.lock().await;
+    let mut locked_triggers = deps.triggers.lock().await;
 
     if !locked_triggers.contains_key(&msg.chat.id) {
         info!("No triggers were found for chat {}", msg.chat.id);
-        bot.send_message(msg.chat.id, "У вас наразі немає тригерів")
+        bot.send_message(msg.chat.id, no_triggers(language))
             .await
             .context("Failed to send Telegram message")?;
 
@@ -402,92 +1147,756 @@ pub async fn delete_all(
 
     if locked_triggers.contains_key(&msg.chat.id) {
         info!("Deleting all triggers for chat {}", msg.chat.id);
-        locked_triggers.remove(&msg.chat.id);
+        locked_triggers.remove_chat(&msg.chat.id);
     }
 
-    if let Err(err) = backup(&locked_triggers) {
+    if let Err(err) = backup(&deps.storage, &locked_triggers) {
         error!("Failed to backup triggers, due to: {}", err);
     }
 
-    bot.send_message(msg.chat.id, "Всі тригери були видалені")
-        .await
-        .context("Failed to send Telegram message")?;
+    bot.send_message(
+        msg.chat.id,
+        language.pick("Всі тригери були видалені", "All triggers were deleted"),
+    )
+    .await
+    .context("Failed to send Telegram message")?;
 
     Ok(())
 }
 
+async fn notify(
+    notifier: &Notifier,
+    storage: &Storage,
+    settings: &Mutex<HashMap<ChatId, settings::Settings>>,
+    chat_id: ChatId,
+    target: &Target,
+    message: impl FnOnce(Language) -> String,
+    now: DateTime<Utc>,
+) -> Result<Delivery, RequestError> {
+    let mut locked_settings = settings.lock().await;
+
+    let chat_settings = locked_settings.get(&chat_id);
+    let quiet_mode = chat_settings.and_then(|x| x.quiet_mode(now));
+    let language = chat_settings.map(|x| x.language).unwrap_or_default();
+    let message = message(language);
+
+    if let Some(QuietMode::Summary) = quiet_mode {
+        info!("Deferring alert for chat {chat_id} until quiet hours are over");
+
+        locked_settings.entry(chat_id).or_default().defer(message);
+
+        if let Err(err) = settings::backup(storage, &locked_settings) {
+            error!("Failed to backup settings, due to: {}", err);
+        }
+
+        return Ok(Delivery::Sent);
+    }
+
+    drop(locked_settings);
+
+    notifier
+        .send(
+            chat_id,
+            &message,
+            Some(&alerts::keyboard(target, language)),
+            quiet_mode.is_some(),
+        )
+        .await
+}
+
+/// Stops evaluating the triggers of chats which the bot can no longer message, putting back
+/// the triggers whose alerts couldn't be delivered. The chats are enabled by their next update
+pub async fn disable_chats(
+    storage: &Storage,
+    triggers: &Mutex<TriggerStore<ChatId>>,
+    chats: HashMap<ChatId, Vec<Trigger>>,
+) {
+    let mut locked_triggers = triggers.lock().await;
+
+    for (chat_id, undelivered) in chats {
+        warn!("Chat {chat_id} is unavailable, disabling its triggers");
+
+        for trigger in undelivered {
+            locked_triggers.insert(chat_id, trigger);
+        }
+
+        locked_triggers.disable(chat_id);
+    }
+
+    if let Err(err) = backup(storage, &locked_triggers) {
+        error!("Failed to backup triggers, due to: {}", err);
+    }
+}
+
+/// Enables the triggers of a chat disabled by [`disable_chats`] once it's heard from again
+pub async fn enable_chat(
+    update: Update,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    storage: Storage,
+) {
+    let Some(chat_id) = update.chat().map(|x| x.id) else {
+        return;
+    };
+
+    let mut locked_triggers = triggers.lock().await;
+
+    if locked_triggers.enable(&chat_id) {
+        info!("Chat {chat_id} is available again, enabling its triggers");
+
+        if let Err(err) = backup(&storage, &locked_triggers) {
+            error!("Failed to backup triggers, due to: {}", err);
+        }
+    }
+}
+
+fn alert_message(
+    trigger: &Trigger,
+    price: price::Price,
+    portfolio: Option<&Portfolio>,
+    language: Language,
+) -> String {
+    let target = trigger.target.describe(language);
+    let current = price::display(price);
+
+    let message = match (&trigger.target, language) {
+        (Target::Lower(_) | Target::Higher(_), Language::Ukrainian) => {
+            format!("Ціна на NEAR зараз {target}\nПоточна ціна: {current}$")
+        }
+        (Target::Lower(_) | Target::Higher(_), Language::English) => {
+            format!("The NEAR price is now {target}\nCurrent price: {current}$")
+        }
+        _ => {
+            let portfolio = portfolio.cloned().unwrap_or_default();
+            let (value, pnl, pnl_percent) = (
+                portfolio.value(price),
+                portfolio.pnl(price),
+                portfolio.pnl_percent(price).unwrap_or_default(),
+            );
+
+            match language {
+                Language::Ukrainian => format!(
+                    "Тригер портфеля спрацював: {target}\n\
+                     Вартість: {value:.2}$, P&L: {pnl:+.2}$ ({pnl_percent:+.2}%)\n\
+                     Поточна ціна: {current}$"
+                ),
+                Language::English => format!(
+                    "Portfolio trigger fired: {target}\n\
+                     Value: {value:.2}$, P&L: {pnl:+.2}$ ({pnl_percent:+.2}%)\n\
+                     Current price: {current}$"
+                ),
+            }
+        }
+    };
+
+    match &trigger.label {
+        Some(label) => format!("{message}\n{}: {label}", language.pick("Підпис", "Label")),
+        None => message,
+    }
+}
+
 pub async fn process(
-    bot: Bot,
-    triggers: Arc<Mutex<HashMap<ChatId, Vec<Trigger>>>>,
+    notifier: Notifier,
+    deps: Deps,
+    history: Arc<Mutex<History>>,
+    feed: FeedMonitor,
 ) -> ResponseResult<()> {
-    let mut interval = interval(Duration::from_millis(200));
+    let Deps {
+        triggers,
+        settings,
+        portfolios,
+        quotes,
+        storage,
+        ..
+    } = deps;
+    let mut interval = interval(PROCESS_INTERVAL);
 
     loop {
         interval.tick().await;
 
-        let locked_triggers = triggers.lock().await;
-        let mut triggered = Vec::new();
-
-        if !locked_triggers.is_empty() {
-            let current_price = price::get().await;
-
-            for (chat_id, triggers_vec) in locked_triggers.iter() {
-                if let Ok(price) = current_price {
-                    for trigger in triggers_vec {
-                        if let &Trigger::Lower(target_price) = trigger {
-                            if price <= target_price {
-                                info!("NEAR price is lower than {target_price:.2}$ for chat {chat_id}");
-
-                                bot.send_message(
-                                    *chat_id,
-                                    format!(
-                                        "Ціна на NEAR зараз менше ніж {target_price:.2}$\nПоточна ціна: {price:.2}$"
-                                    ),
-                                )
-                                .await?;
-
-                                triggered.push((*chat_id, trigger.clone()));
-                            }
-                        } else if let &Trigger::Higher(target_price) = trigger {
-                            if price >= target_price {
-                                info!("NEAR price is higher than {target_price:.2}$ for chat {chat_id}");
-
-                                bot.send_message(
-                                    *chat_id,
-                                    format!(
-                                        "Ціна на NEAR зараз більше ніж {target_price:.2}$\nПоточна ціна: {price:.2}$"
-                                    ),
-                                )
-                                .await?;
-
-                                triggered.push((*chat_id, trigger.clone()));
-                            }
-                        }
+        let mut locked_triggers = triggers.lock().await;
+
+        // Without triggers no quote is fetched, so the feed status is left as it is
+        if locked_triggers.is_empty() {
+            continue;
+        }
+
+        let quote = quotes.quote(PROCESS_INTERVAL).await.ok();
+
+        if quote.is_some_and(|x| x.age() <= PROCESS_INTERVAL) {
+            feed.record_success().await;
+        } else {
+            let locked_settings = settings.lock().await;
+            let chats = locked_triggers
+                .keys()
+                .filter(|x| !locked_triggers.is_disabled(x))
+                .map(|x| {
+                    let language = locked_settings.get(x).map(|x| x.language);
+                    (*x, language.unwrap_or_default())
+                })
+                .collect::<Vec<_>>();
+            drop(locked_settings);
+
+            feed.record_failure(chats).await;
+        }
+
+        let Some(quote) = quote.filter(|x| !x.is_stale()) else {
+            continue;
+        };
+
+        let price = quote.price;
+
+        let now = Utc::now();
+
+        let (armed, fired) = {
+            let locked_portfolios = portfolios.lock().await;
+            let (mut armed, mut fired) = (Vec::new(), Vec::new());
+
+            for event in engine::evaluate(&locked_triggers, &locked_portfolios, price, now) {
+                match event {
+                    engine::Event::Fired {
+                        chat_id,
+                        target,
+                        observed,
+                    } => {
+                        info!("Trigger {target:?} reached {observed:.2} for chat {chat_id}");
+
+                        let trigger = locked_triggers
+                            .get(&chat_id)
+                            .into_iter()
+                            .flatten()
+                            .find(|x| x.target.is_same(&target))
+                            .cloned()
+                            .unwrap_or_else(|| Trigger::new(target.clone()));
+
+                        let portfolio = locked_portfolios.get(&chat_id).cloned();
+                        fired.push((chat_id, trigger, portfolio));
+                    }
+                    engine::Event::Armed { chat_id, target } => {
+                        info!("Trigger {target:?} armed for chat {chat_id}");
+
+                        armed.push(engine::Event::Armed { chat_id, target });
                     }
+                    engine::Event::Expired { .. } => {}
+                }
+            }
+
+            (armed, fired)
+        };
+
+        if !armed.is_empty() {
+            engine::apply(&mut locked_triggers, &armed);
+
+            if fired.is_empty() {
+                if let Err(err) = backup(&storage, &locked_triggers) {
+                    error!("Failed to backup triggers, due to: {}", err);
                 }
             }
+        }
+
+        if fired.is_empty() {
+            continue;
+        }
+
+        let triggered = fired
+            .iter()
+            .map(|(chat_id, trigger, _)| (*chat_id, trigger.target.clone()))
+            .collect();
+        remove_triggered(&storage, triggered, locked_triggers);
+
+        let mut locked_history = history.lock().await;
+
+        for (chat_id, trigger, _) in &fired {
+            Metrics::inc(if trigger.target.is_lower() {
+                &METRICS.triggers_fired_lower
+            } else {
+                &METRICS.triggers_fired_higher
+            });
+            METRICS.record_fire();
+
+            locked_history.record(
+                *chat_id,
+                history::Entry {
+                    target: trigger.target.clone(),
+                    price,
+                    fired_at: now,
+                    source: String::from(price::SOURCE),
+                    label: trigger.label.clone(),
+                    expires_at: trigger.expires_at,
+                },
+            );
+        }
 
-            if !triggered.is_empty() {
-                remove_triggered(triggered, locked_triggers);
+        if let Err(err) = locked_history.backup(&storage) {
+            error!("Failed to backup history, due to: {}", err);
+        }
+
+        drop(locked_history);
+
+        let mut unavailable = HashMap::<ChatId, Vec<Trigger>>::new();
+
+        for (chat_id, trigger, portfolio) in fired {
+            if let Some(undelivered) = unavailable.get_mut(&chat_id) {
+                undelivered.push(trigger);
+                continue;
+            }
+
+            match notify(
+                &notifier,
+                &storage,
+                &settings,
+                chat_id,
+                &trigger.target,
+                |language| alert_message(&trigger, price, portfolio.as_ref(), language),
+                now,
+            )
+            .await
+            {
+                Ok(Delivery::Sent) => {}
+                Ok(Delivery::ChatUnavailable) => {
+                    unavailable.insert(chat_id, vec![trigger]);
+                }
+                Err(err) => error!(
+                    "Failed to notify chat {chat_id} about {:?}: {err}",
+                    trigger.target
+                ),
             }
         }
+
+        if !unavailable.is_empty() {
+            disable_chats(&storage, &triggers, unavailable).await;
+        }
     }
 }
 
-fn backup(triggers: &HashMap<ChatId, Vec<Trigger>>) -> Result<()> {
-    info!("Backing up triggers...");
+pub async fn sweep(
+    notifier: Notifier,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    settings: Arc<Mutex<HashMap<ChatId, settings::Settings>>>,
+    storage: Storage,
+) {
+    let mut interval = interval(SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
 
-    let content = serialize(triggers)?;
-    write("triggers.bak", content)?;
+        let locked_triggers = triggers.lock().await;
+        let now = Utc::now();
 
-    Ok(())
+        let expired = engine::expire(&locked_triggers, now)
+            .into_iter()
+            .map(|x| (x.chat_id(), x.target().clone()))
+            .collect::<Vec<_>>();
+
+        if expired.is_empty() {
+            continue;
+        }
+
+        remove_triggered(&storage, expired.clone(), locked_triggers);
+
+        for (chat_id, target) in expired {
+            info!("Trigger {target:?} expired for chat {chat_id}");
+
+            let message = match settings::language(&settings, chat_id).await {
+                Language::Ukrainian => format!(
+                    "Термін дії тригера {} минув, тригер не спрацював",
+                    target.describe(Language::Ukrainian)
+                ),
+                Language::English => format!(
+                    "The trigger {} expired without firing",
+                    target.describe(Language::English)
+                ),
+            };
+
+            if let Err(err) = notifier.send(chat_id, &message, None, false).await {
+                error!("Failed to notify chat {chat_id} about expired trigger: {err}");
+            }
+        }
+    }
+}
+
+pub fn backup(storage: &Storage, triggers: &TriggerStore<ChatId>) -> Result<()> {
+    info!("Backing up triggers...");
+
+    storage.save("triggers.bak", BACKUP_VERSION, triggers)
 }
 
-pub fn restore() -> Result<HashMap<ChatId, Vec<Trigger>>> {
+pub fn restore(storage: &Storage) -> Result<TriggerStore<ChatId>> {
     info!("Restoring triggers...");
 
-    let content = read("triggers.bak")?;
-    let triggers = deserialize(&content)?;
+    let content = match storage.load_or_baseline("triggers.bak", BACKUP_VERSION)? {
+        Backup::Current(triggers) => return Ok(triggers),
+        Backup::Baseline(content) => content,
+    };
+
+    warn!("Converting triggers from the backup format of the first release");
+
+    let baseline: HashMap<ChatId, Vec<BaselineTrigger>> = deserialize(&content)?;
+    let triggers = baseline
+        .into_iter()
+        .map(|(chat_id, triggers)| {
+            let triggers = triggers
+                .into_iter()
+                .filter_map(BaselineTrigger::into_trigger)
+                .collect();
+
+            (chat_id, triggers)
+        })
+        .collect();
 
     Ok(triggers)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::feed::FeedMonitor;
+    use crate::testing::{temp_storage, Harness, CHAT_ID};
+
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    /// `{ChatId(42): [Lower(6.5), Higher(7.25), Neutral(1.0)]}` as the first release wrote it
+    const BASELINE_BACKUP: [u8; 60] = [
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // chats
+        0x2a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // ChatId(42)
+        0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // triggers
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1a, 0x40, // Lower(6.5)
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1d,
+        0x40, // Higher(7.25)
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0,
+        0x3f, // Neutral(1.0)
+    ];
+
+    #[test]
+    fn restores_baseline_backup() {
+        let storage = temp_storage();
+        std::fs::write(storage.path("triggers.bak"), BASELINE_BACKUP).unwrap();
+
+        let check = |triggers: &TriggerStore<ChatId>| {
+            assert_eq!(triggers.trigger_count(), 2);
+
+            let chat_triggers = triggers.get(&ChatId(CHAT_ID)).unwrap();
+            assert!(chat_triggers.iter().all(|x| x.armed && x.label.is_none()));
+            assert!(chat_triggers
+                .iter()
+                .any(|x| x.target == Target::Lower(dec!(6.5))));
+            assert!(chat_triggers
+                .iter()
+                .any(|x| x.target == Target::Higher(dec!(7.25))));
+        };
+
+        let triggers = restore(&storage).unwrap();
+        check(&triggers);
+
+        // The next backup is written in the current format and restores unchanged
+        backup(&storage, &triggers).unwrap();
+        assert!(std::fs::read(storage.path("triggers.bak"))
+            .unwrap()
+            .starts_with(b"NPTB"));
+        check(&restore(&storage).unwrap());
+    }
+
+    #[test]
+    fn parses_expiry() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        let at = |y, m, d, h, min| Some(Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap());
+
+        let cases = [
+            ("30m", Tz::UTC, Some(now + chrono::Duration::minutes(30))),
+            ("for 24h", Tz::UTC, Some(now + chrono::Duration::hours(24))),
+            (" 7D ", Tz::UTC, Some(now + chrono::Duration::days(7))),
+            ("365d", Tz::UTC, Some(now + chrono::Duration::days(365))),
+            ("366d", Tz::UTC, None),
+            ("0m", Tz::UTC, None),
+            ("-5h", Tz::UTC, None),
+            ("7w", Tz::UTC, None),
+            ("", Tz::UTC, None),
+            ("2026-03-01", Tz::UTC, at(2026, 3, 1, 0, 0)),
+            ("until 2026-03-01", Tz::Europe__Kyiv, at(2026, 2, 28, 22, 0)),
+            ("2026-07-01", Tz::Europe__Kyiv, at(2026, 6, 30, 21, 0)),
+            ("2026-03-01", Tz::America__New_York, at(2026, 3, 1, 5, 0)),
+            ("2026-03-01", Tz::Asia__Kolkata, at(2026, 2, 28, 18, 30)),
+            // Daylight saving time starts at midnight, so the day starts at 01:00
+            ("2026-03-08", Tz::America__Havana, at(2026, 3, 8, 5, 0)),
+            // Midnight has already passed in the chat's time zone
+            ("2026-01-01", Tz::UTC, None),
+            ("2026-01-02", Tz::Pacific__Kiritimati, None),
+            ("2027-01-01", Tz::UTC, at(2027, 1, 1, 0, 0)),
+            ("2027-01-02", Tz::UTC, None),
+            ("9999-12-31", Tz::UTC, None),
+            ("2026-02-30", Tz::UTC, None),
+        ];
+
+        for (text, time_zone, expected) in cases {
+            assert_eq!(
+                parse_expiry(text, now, time_zone),
+                expected,
+                "{text:?} in {time_zone}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap();
+        let day = Some(now + chrono::Duration::days(1));
+        let label = |x: &str| Some(String::from(x));
+
+        let cases = [
+            ("> 7.5", Some((Target::Higher(dec!(7.5)), None, None))),
+            ("<7,25$", Some((Target::Lower(dec!(7.25)), None, None))),
+            ("> 7.5 24h", Some((Target::Higher(dec!(7.5)), day, None))),
+            (
+                "< 6 24h stop  loss",
+                Some((Target::Lower(dec!(6)), day, label("stop loss"))),
+            ),
+            (
+                "> 7.5 take profit",
+                Some((Target::Higher(dec!(7.5)), None, label("take profit"))),
+            ),
+            (
+                "> 7.5 2026-03-01",
+                Some((
+                    Target::Higher(dec!(7.5)),
+                    Some(Utc.with_ymd_and_hms(2026, 2, 28, 22, 0, 0).unwrap()),
+                    None,
+                )),
+            ),
+            // Too far out to be an expiry, so it's taken as the label
+            (
+                "> 7.5 9999-12-31",
+                Some((Target::Higher(dec!(7.5)), None, label("9999-12-31"))),
+            ),
+            (
+                "value < 1000",
+                Some((Target::ValueLower(dec!(1000)), None, None)),
+            ),
+            (
+                "pnl > 20% 24h",
+                Some((Target::PnlHigher(dec!(20)), day, None)),
+            ),
+            ("7.5", None),
+            ("> abc", None),
+            ("< -5", None),
+            ("> 0", None),
+            ("> 0.0004", None),
+            ("value < 0", None),
+            ("value", None),
+            (&format!("> 7.5 {}", "a".repeat(MAX_LABEL_LENGTH + 1)), None),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(
+                parse_arguments(args, now, Tz::Europe__Kyiv).map(|x| (
+                    x.target,
+                    x.expires_at,
+                    x.label
+                )),
+                expected,
+                "{args:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_alert_when_trigger_fires() {
+        let harness = Harness::new();
+        harness.triggers.lock().await.insert(
+            ChatId(CHAT_ID),
+            Trigger {
+                label: Some(String::from("stop loss")),
+                ..Trigger::new(Target::Lower(dec!(6.2)))
+            },
+        );
+
+        harness.set_price(dec!(6.1)).await;
+
+        let task = tokio::spawn(process(
+            harness.notifier.clone(),
+            harness.deps(),
+            harness.history.clone(),
+            FeedMonitor::new(harness.notifier.clone(), None, Duration::from_secs(60)),
+        ));
+
+        let alert = harness.last_message().await;
+        task.abort();
+
+        assert_eq!(
+            alert.text(),
+            Some("Ціна на NEAR зараз менше ніж 6.20$\nПоточна ціна: 6.10$\nПідпис: stop loss")
+        );
+        assert_eq!(
+            alert.buttons(),
+            [
+                vec!["Повторити", "Відкласти на 1 год"],
+                vec!["-5%", "+5%"],
+                vec!["Видалити"],
+            ]
+        );
+        assert!(harness.triggers.lock().await.is_empty());
+        assert_eq!(
+            harness
+                .history
+                .lock()
+                .await
+                .recent(ChatId(CHAT_ID), 10)
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn defers_alerts_during_quiet_hours() {
+        let harness = Harness::new();
+        let night = Utc.with_ymd_and_hms(2026, 1, 1, 23, 30, 0).unwrap();
+        let target = Target::Lower(dec!(6.2));
+
+        harness.settings.lock().await.insert(
+            ChatId(CHAT_ID),
+            settings::Settings {
+                quiet_hours: true,
+                quiet_mode: QuietMode::Summary,
+                ..settings::Settings::default()
+            },
+        );
+
+        let notify = |message: &'static str, now| {
+            notify(
+                &harness.notifier,
+                &harness.storage,
+                &harness.settings,
+                ChatId(CHAT_ID),
+                &target,
+                move |_| String::from(message),
+                now,
+            )
+        };
+
+        notify("deferred", night).await.unwrap();
+        assert!(harness.take_requests().await.is_empty());
+        assert_eq!(
+            harness.settings.lock().await[&ChatId(CHAT_ID)].deferred,
+            ["deferred"]
+        );
+        assert_eq!(
+            settings::restore(&harness.storage).unwrap()[&ChatId(CHAT_ID)].deferred,
+            ["deferred"]
+        );
+
+        harness
+            .settings
+            .lock()
+            .await
+            .get_mut(&ChatId(CHAT_ID))
+            .unwrap()
+            .quiet_mode = QuietMode::Silent;
+
+        notify("silent", night).await.unwrap();
+        let alert = harness.last_message().await;
+        assert_eq!(alert.text(), Some("silent"));
+        assert_eq!(alert.body["disable_notification"], true);
+
+        notify("morning", night + chrono::Duration::hours(9))
+            .await
+            .unwrap();
+        let alert = harness.last_message().await;
+        assert_eq!(alert.text(), Some("morning"));
+        assert_ne!(alert.body["disable_notification"], true);
+    }
+
+    #[tokio::test]
+    async fn disables_unavailable_chats() {
+        let mut harness = Harness::new();
+        {
+            let mut triggers = harness.triggers.lock().await;
+            triggers.insert(ChatId(CHAT_ID), Trigger::new(Target::Lower(dec!(6.2))));
+            triggers.insert(ChatId(CHAT_ID), Trigger::new(Target::Higher(dec!(9))));
+            triggers.insert(ChatId(7), Trigger::new(Target::Higher(dec!(9))));
+        }
+        harness
+            .fail_next(serde_json::json!({
+                "ok": false,
+                "error_code": 403,
+                "description": "Forbidden: bot was blocked by the user",
+            }))
+            .await;
+
+        harness.set_price(dec!(6.1)).await;
+
+        let task = tokio::spawn(process(
+            harness.notifier.clone(),
+            harness.deps(),
+            harness.history.clone(),
+            FeedMonitor::new(harness.notifier.clone(), None, Duration::from_secs(60)),
+        ));
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !harness.triggers.lock().await.is_disabled(&ChatId(CHAT_ID)) {
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "Chat was not disabled"
+            );
+            tokio::time::sleep(PROCESS_INTERVAL).await;
+        }
+
+        // The undelivered trigger is kept and not retried while the chat is disabled
+        tokio::time::sleep(PROCESS_INTERVAL * 5).await;
+        assert_eq!(harness.take_requests().await.len(), 1);
+
+        let restored = restore(&harness.storage).unwrap();
+        assert!(restored.is_disabled(&ChatId(CHAT_ID)));
+        assert_eq!(restored.trigger_count(), 3);
+
+        // Any update from the chat enables it and the alert is delivered
+        harness.send("/listtriggers").await;
+        assert!(!harness.triggers.lock().await.is_disabled(&ChatId(CHAT_ID)));
+
+        let alert = loop {
+            let message = harness.last_message().await;
+
+            if message
+                .text()
+                .is_some_and(|x| x.starts_with("Ціна на NEAR"))
+            {
+                break message;
+            }
+        };
+        task.abort();
+
+        assert_eq!(
+            alert.text(),
+            Some("Ціна на NEAR зараз менше ніж 6.20$\nПоточна ціна: 6.10$")
+        );
+        assert!(!restore(&harness.storage)
+            .unwrap()
+            .is_disabled(&ChatId(CHAT_ID)));
+    }
+
+    #[tokio::test]
+    async fn ignores_stale_quotes() {
+        let harness = Harness::new();
+        harness
+            .triggers
+            .lock()
+            .await
+            .insert(ChatId(CHAT_ID), Trigger::new(Target::Lower(dec!(6.2))));
+
+        let fetched_at = std::time::Instant::now()
+            .checked_sub(price::STALE_AFTER * 2)
+            .unwrap();
+        harness.quotes.set(dec!(6.1), fetched_at).await;
+
+        let task = tokio::spawn(process(
+            harness.notifier.clone(),
+            harness.deps(),
+            harness.history.clone(),
+            FeedMonitor::new(harness.notifier.clone(), None, Duration::from_secs(60)),
+        ));
+
+        tokio::time::sleep(PROCESS_INTERVAL * 5).await;
+        task.abort();
+
+        assert!(harness.take_requests().await.is_empty());
+        assert_eq!(harness.triggers.lock().await.trigger_count(), 1);
+    }
+}
@@ -0,0 +1,295 @@
+//! /start onboarding: new chats get a welcome with quick-pick thresholds and a choice of language,
+//! returning ones a summary of their triggers
+
+use crate::commands::{
+    callback::{Callback, TriggerKind},
+    menu, price,
+    settings::{self, Language},
+    triggers::{self, Trigger},
+    Deps, HandlerResult, MyDialogue,
+};
+use crate::config::Config;
+
+use anyhow::Context;
+use log::{error, info, warn};
+
+use rust_decimal::Decimal;
+
+use teloxide::{prelude::*, types::InlineKeyboardMarkup};
+
+/// Distances of the quick-pick thresholds from the current price, in percent
+const QUICK_PICK_PERCENTS: [i64; 2] = [10, 5];
+
+/// Number of triggers listed in the summary for returning chats
+const SUMMARY_TRIGGERS: usize = 5;
+
+/// Thresholds around the current price, the lower ones first
+fn quick_picks(price: price::Price) -> Vec<Trigger> {
+    let level = |percent: i64| price * Decimal::from(100 + percent) / Decimal::ONE_HUNDRED;
+
+    let lower = QUICK_PICK_PERCENTS
+        .iter()
+        .map(|x| TriggerKind::Lower.target(level(-x)));
+    let higher = QUICK_PICK_PERCENTS
+        .iter()
+        .rev()
+        .map(|x| TriggerKind::Higher.target(level(*x)));
+
+    lower.chain(higher).map(Trigger::new).collect()
+}
+
+fn keyboard(price: Option<price::Price>) -> InlineKeyboardMarkup {
+    let quick_picks = price.map(quick_picks).unwrap_or_default();
+    let percents = QUICK_PICK_PERCENTS
+        .iter()
+        .map(|x| -x)
+        .chain(QUICK_PICK_PERCENTS.iter().rev().copied());
+
+    let buttons = quick_picks
+        .into_iter()
+        .zip(percents)
+        .map(|(trigger, percent)| {
+            let text = format!("{percent:+}% ({}$)", price::display(trigger.target.price()));
+            Callback::QuickTrigger(trigger.target).button(text)
+        })
+        .collect::<Vec<_>>();
+
+    let mut rows = buttons
+        .chunks(QUICK_PICK_PERCENTS.len())
+        .map(<[_]>::to_vec)
+        .collect::<Vec<_>>();
+
+    rows.push(vec![
+        Callback::Language(Language::Ukrainian).button("Українська"),
+        Callback::Language(Language::English).button("English"),
+    ]);
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+fn price_line(language: Language, price: Option<price::Price>) -> String {
+    match (language, price) {
+        (Language::Ukrainian, Some(price)) => {
+            format!("Поточна ціна NEAR: {}$", price::display(price))
+        }
+        (Language::English, Some(price)) => {
+            format!("Current NEAR price: {}$", price::display(price))
+        }
+        (Language::Ukrainian, None) => String::from("Не вдалося отримати поточну ціну NEAR"),
+        (Language::English, None) => String::from("Couldn't get the current NEAR price"),
+    }
+}
+
+fn welcome(language: Language, price: Option<price::Price>) -> String {
+    let price_line = price_line(language, price);
+
+    match language {
+        Language::Ukrainian => {
+            let next_step = if price.is_some() {
+                "Оберіть поріг нижче, щоб створити перший тригер, або додайте свій через /addtrigger"
+            } else {
+                "Додайте перший тригер через /addtrigger"
+            };
+
+            format!(
+                "Вітаю! Я стежу за ціною NEAR і надсилаю сповіщення, коли вона досягає обраного рівня.\n\n\
+                 {price_line}\n\n\
+                 {next_step}\n\
+                 Усі команди: /help\n\
+                 Мову можна змінити кнопками нижче або в /settings"
+            )
+        }
+        Language::English => {
+            let next_step = if price.is_some() {
+                "Pick a threshold below to create your first trigger, or add your own with /addtrigger"
+            } else {
+                "Add your first trigger with /addtrigger"
+            };
+
+            format!(
+                "Welcome! I watch the NEAR price and notify you when it reaches the level you choose.\n\n\
+                 {price_line}\n\n\
+                 {next_step}\n\
+                 All commands: /help\n\
+                 The language can be changed with the buttons below or in /settings"
+            )
+        }
+    }
+}
+
+fn summary(language: Language, price: Option<price::Price>, chat_triggers: &[Trigger]) -> String {
+    let (greeting, empty, title, all) = match language {
+        Language::Ukrainian => (
+            "З поверненням!",
+            "У вас наразі немає тригерів, додайте їх через /addtrigger",
+            "Ваші тригери",
+            "Усі тригери: /listtriggers, команди: /help",
+        ),
+        Language::English => (
+            "Welcome back!",
+            "You have no triggers yet, add one with /addtrigger",
+            "Your triggers",
+            "All triggers: /listtriggers, commands: /help",
+        ),
+    };
+
+    let mut message = format!("{greeting} {}\n\n", price_line(language, price));
+
+    if chat_triggers.is_empty() {
+        message.push_str(empty);
+        return message;
+    }
+
+    message.push_str(&format!("{title} ({}):\n", chat_triggers.len()));
+
+    for trigger in chat_triggers.iter().take(SUMMARY_TRIGGERS) {
+        message.push_str(&format!("{}\n", trigger.describe(language)));
+    }
+
+    if chat_triggers.len() > SUMMARY_TRIGGERS {
+        let hidden = chat_triggers.len() - SUMMARY_TRIGGERS;
+
+        match language {
+            Language::Ukrainian => message.push_str(&format!("… і ще {hidden}\n")),
+            Language::English => message.push_str(&format!("… and {hidden} more\n")),
+        }
+    }
+
+    message.push_str(all);
+
+    message
+}
+
+async fn current_price(quotes: &price::Quotes) -> Option<price::Price> {
+    quotes
+        .quote(price::QUOTE_MAX_AGE)
+        .await
+        .ok()
+        .filter(|x| !x.is_stale())
+        .map(|x| x.price)
+}
+
+pub async fn start(bot: Bot, msg: Message, deps: Deps, language: Language) -> HandlerResult {
+    info!("Receiving start command...");
+
+    let chat_id = msg.chat.id;
+    let chat_triggers = deps
+        .triggers
+        .lock()
+        .await
+        .get(&chat_id)
+        .cloned()
+        .unwrap_or_default();
+
+    // A chat counts as known once it has settings, which /start creates, or triggers from before onboarding existed.
+    // New chats start in the language of the client, which the welcome lets them change
+    let is_new = {
+        let mut locked_settings = deps.settings.lock().await;
+        let is_new = !locked_settings.contains_key(&chat_id) && chat_triggers.is_empty();
+
+        if is_new {
+            settings::chat_entry(&mut locked_settings, chat_id, language);
+
+            if let Err(err) = settings::backup(&deps.storage, &locked_settings) {
+                error!("Failed to backup settings, due to: {}", err);
+            }
+        }
+
+        is_new
+    };
+
+    let price = current_price(&deps.quotes).await;
+    let message = if is_new {
+        info!("Welcoming new chat {chat_id}");
+        welcome(language, price)
+    } else {
+        summary(language, price, &chat_triggers)
+    };
+
+    bot.send_message(chat_id, message)
+        .reply_markup(keyboard(price))
+        .await
+        .context("Failed to send Telegram message")?;
+
+    Ok(())
+}
+
+pub fn is_callback(q: CallbackQuery) -> bool {
+    matches!(
+        Callback::from_query(&q),
+        Some(Callback::QuickTrigger(_) | Callback::Language(_))
+    )
+}
+
+/// Stores the language chosen in the welcome, switches the chat menu to it and shows the message in it
+async fn choose_language(
+    bot: Bot,
+    message: &Message,
+    language: Language,
+    deps: &Deps,
+    config: &Config,
+) -> HandlerResult {
+    let chat_id = message.chat.id;
+
+    info!("Chat {chat_id} chose {language:?}");
+
+    {
+        let mut locked_settings = deps.settings.lock().await;
+        settings::chat_entry(&mut locked_settings, chat_id, language).language = language;
+
+        if let Err(err) = settings::backup(&deps.storage, &locked_settings) {
+            error!("Failed to backup settings, due to: {}", err);
+        }
+    }
+
+    menu::register_chat(&bot, &message.chat, config, language).await;
+
+    let chat_triggers = deps
+        .triggers
+        .lock()
+        .await
+        .get(&chat_id)
+        .cloned()
+        .unwrap_or_default();
+    let price = current_price(&deps.quotes).await;
+
+    let text = if chat_triggers.is_empty() {
+        welcome(language, price)
+    } else {
+        summary(language, price, &chat_triggers)
+    };
+
+    bot.edit_message_text(chat_id, message.id, text)
+        .reply_markup(keyboard(price))
+        .await
+        .context("Failed to edit Telegram message")?;
+
+    Ok(())
+}
+
+pub async fn process(
+    bot: Bot,
+    dialogue: MyDialogue,
+    q: CallbackQuery,
+    deps: Deps,
+    config: Config,
+    language: Language,
+) -> HandlerResult {
+    info!("Receiving onboarding action...");
+
+    bot.answer_callback_query(q.id.clone())
+        .await
+        .context("Failed to answer callback query")?;
+
+    match (Callback::from_query(&q), q.message) {
+        (Some(Callback::QuickTrigger(target)), _) => {
+            triggers::add(bot, &dialogue, Trigger::new(target), &deps, language).await?;
+        }
+        (Some(Callback::Language(chosen)), Some(message)) => {
+            choose_language(bot, &message, chosen, &deps, &config).await?;
+        }
+        _ => warn!("Invalid onboarding callback query: {:?}", q.data),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use log::warn;
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use teloxide::types::{ChatId, UserId};
+use url::Url;
+
+#[derive(Clone)]
+pub struct WebhookConfig {
+    /// Address the webhook listener binds to
+    pub addr: SocketAddr,
+    /// Public URL which Telegram sends updates to, e.g. behind a reverse proxy
+    pub url: Url,
+    /// Value of the `X-Telegram-Bot-Api-Secret-Token` header expected on every update
+    pub secret: Option<String>,
+}
+
+/// How much alert history is kept per chat
+#[derive(Clone, Copy)]
+pub struct HistoryConfig {
+    pub max_entries: usize,
+    pub max_age_days: i64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 100,
+            max_age_days: 90,
+        }
+    }
+}
+
+/// Protection against chats adding too many triggers or flooding the bot with commands
+#[derive(Clone, Copy)]
+pub struct LimitsConfig {
+    pub max_triggers_per_chat: usize,
+    /// Triggers of all chats together
+    pub max_triggers: usize,
+    pub commands_per_minute: u32,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_triggers_per_chat: 20,
+            max_triggers: 10_000,
+            commands_per_minute: 20,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Config {
+    /// Chat which receives operational notifications, e.g. about crashed background tasks
+    pub admin_chat_id: Option<ChatId>,
+    /// Users allowed to run admin commands
+    pub admin_ids: Vec<UserId>,
+    /// Address of the health check and metrics HTTP server
+    pub http_addr: Option<SocketAddr>,
+    /// Receive updates via webhook instead of long polling
+    pub webhook: Option<WebhookConfig>,
+    pub history: HistoryConfig,
+    /// The price feed is considered down after this long without a fresh quote
+    pub feed_outage_after: Duration,
+    pub limits: LimitsConfig,
+    /// Directory the backup files are kept in
+    pub data_dir: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            admin_chat_id: None,
+            admin_ids: Vec::new(),
+            http_addr: None,
+            webhook: None,
+            history: HistoryConfig::default(),
+            feed_outage_after: Duration::from_secs(60),
+            limits: LimitsConfig::default(),
+            data_dir: PathBuf::from("."),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            admin_chat_id: parse_var("ADMIN_CHAT_ID").map(ChatId),
+            admin_ids: parse_list("ADMIN_IDS").into_iter().map(UserId).collect(),
+            http_addr: parse_var("HTTP_ADDR"),
+            webhook: parse_var("WEBHOOK_URL").map(|url| WebhookConfig {
+                addr: parse_var("WEBHOOK_ADDR")
+                    .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 8443))),
+                url,
+                secret: env::var("WEBHOOK_SECRET").ok(),
+            }),
+            history: HistoryConfig {
+                max_entries: parse_var("HISTORY_MAX_ENTRIES")
+                    .unwrap_or(HistoryConfig::default().max_entries),
+                max_age_days: parse_var("HISTORY_MAX_AGE_DAYS")
+                    .filter(|x| *x > 0)
+                    .unwrap_or(HistoryConfig::default().max_age_days),
+            },
+            feed_outage_after: parse_var("FEED_OUTAGE_SECS")
+                .map_or(Self::default().feed_outage_after, Duration::from_secs),
+            limits: LimitsConfig {
+                max_triggers_per_chat: parse_var("MAX_TRIGGERS_PER_CHAT")
+                    .unwrap_or(LimitsConfig::default().max_triggers_per_chat),
+                max_triggers: parse_var("MAX_TRIGGERS")
+                    .unwrap_or(LimitsConfig::default().max_triggers),
+                commands_per_minute: parse_var("COMMANDS_PER_MINUTE")
+                    .filter(|x| *x > 0)
+                    .unwrap_or(LimitsConfig::default().commands_per_minute),
+            },
+            data_dir: env::var_os("DATA_DIR").map_or(Self::default().data_dir, PathBuf::from),
+        }
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok()?;
+
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            warn!("Ignoring invalid value of {name}: {value:?}");
+            None
+        }
+    }
+}
+
+fn parse_list<T: std::str::FromStr>(name: &str) -> Vec<T> {
+    let Ok(value) = env::var(name) else {
+        return Vec::new();
+    };
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .filter_map(|x| match x.parse() {
+            Ok(x) => Some(x),
+            Err(_) => {
+                warn!("Ignoring invalid item of {name}: {x:?}");
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,11 @@
+//! Library part of the bot, shared by the binary and the benchmarks
+
+pub mod commands;
+pub mod config;
+pub mod metrics;
+pub mod replay;
+pub mod server;
+pub mod supervisor;
+#[cfg(test)]
+mod testing;
+pub mod webhook;
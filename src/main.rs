@@ -1,4 +1,14 @@
-use crate::commands::{schema, triggers, State};
+use near_price_tracking_bot::commands::{
+    feed::FeedMonitor,
+    history::History,
+    limits::{self, Limits},
+    menu,
+    notifier::Notifier,
+    portfolio, price, schema, settings,
+    storage::Storage,
+    triggers, Deps, State,
+};
+use near_price_tracking_bot::{config::Config, replay, server, supervisor, webhook};
 
 use anyhow::Result;
 
@@ -8,31 +18,148 @@ use tokio::sync::Mutex;
 use teloxide::dispatching::dialogue::InMemStorage;
 use teloxide::prelude::*;
 
-mod commands;
-
 #[tokio::main]
 async fn main() -> Result<()> {
     pretty_env_logger::init_timed();
+
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("replay") {
+        return replay::run(&args[2..]);
+    }
+
     log::info!("Starting near price notifier bot...");
 
     let bot = Bot::from_env();
+    let config = Config::from_env();
+    let storage = Storage::new(config.data_dir.clone());
 
-    let triggers = match triggers::restore() {
+    let triggers = match triggers::restore(&storage) {
         Ok(triggers) => Arc::new(Mutex::new(triggers)),
         Err(err) => {
             log::error!("Failed to restore triggers: {}", err);
+            Arc::default()
+        }
+    };
+
+    let settings = match settings::restore(&storage) {
+        Ok(settings) => Arc::new(Mutex::new(settings)),
+        Err(err) => {
+            log::error!("Failed to restore settings: {}", err);
             Arc::new(Mutex::new(HashMap::new()))
         }
     };
 
-    tokio::spawn(triggers::process(bot.clone(), triggers.clone()));
+    let portfolios = match portfolio::restore(&storage) {
+        Ok(portfolios) => Arc::new(Mutex::new(portfolios)),
+        Err(err) => {
+            log::error!("Failed to restore portfolios: {}", err);
+            Arc::new(Mutex::new(HashMap::new()))
+        }
+    };
+
+    let history = match History::restore(&storage, config.history) {
+        Ok(history) => Arc::new(Mutex::new(history)),
+        Err(err) => {
+            log::error!("Failed to restore history: {}", err);
+            Arc::new(Mutex::new(History::new(config.history)))
+        }
+    };
+
+    let limits = match limits::restore(&storage) {
+        Ok(quotas) => Limits::new(config.limits, quotas, storage.clone()),
+        Err(err) => {
+            log::error!("Failed to restore trigger quotas: {}", err);
+            Limits::new(config.limits, HashMap::new(), storage.clone())
+        }
+    };
+
+    let quotes = price::Quotes::new(price::Binance);
+    let notifier = Notifier::new(bot.clone());
+    let feed = FeedMonitor::new(
+        notifier.clone(),
+        config.admin_chat_id,
+        config.feed_outage_after,
+    );
+
+    tokio::spawn(supervisor::supervise(
+        "trigger processing",
+        notifier.clone(),
+        config.admin_chat_id,
+        {
+            let (notifier, history) = (notifier.clone(), history.clone());
+            let deps = Deps::new(
+                triggers.clone(),
+                settings.clone(),
+                portfolios.clone(),
+                limits.clone(),
+                quotes.clone(),
+                storage.clone(),
+            );
+            move || {
+                triggers::process(
+                    notifier.clone(),
+                    deps.clone(),
+                    history.clone(),
+                    feed.clone(),
+                )
+            }
+        },
+    ));
+    if let Some(addr) = config.http_addr {
+        let (bot, triggers, quotes) = (bot.clone(), triggers.clone(), quotes.clone());
 
-    Dispatcher::builder(bot, schema::process())
-        .dependencies(dptree::deps![InMemStorage::<State>::new(), triggers])
+        tokio::spawn(async move {
+            if let Err(err) = server::serve(addr, bot, triggers, quotes).await {
+                log::error!("{err:#}");
+            }
+        });
+    }
+
+    tokio::spawn(triggers::sweep(
+        notifier.clone(),
+        triggers.clone(),
+        settings.clone(),
+        storage.clone(),
+    ));
+    tokio::spawn(settings::deliver_summaries(
+        notifier.clone(),
+        settings.clone(),
+        storage.clone(),
+    ));
+
+    menu::register(&bot, &config, &storage, &*settings.lock().await).await;
+
+    let webhook = config.webhook.clone();
+
+    let mut dispatcher = Dispatcher::builder(bot.clone(), schema::process())
+        .dependencies(dptree::deps![
+            InMemStorage::<State>::new(),
+            triggers,
+            settings,
+            history,
+            portfolios,
+            notifier,
+            limits,
+            quotes,
+            storage,
+            config
+        ])
         .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    match webhook {
+        Some(webhook) => {
+            let listener = webhook::listener(bot, &webhook).await?;
+
+            dispatcher
+                .dispatch_with_listener(
+                    listener,
+                    LoggingErrorHandler::with_custom_text("An error from the webhook listener"),
+                )
+                .await;
+        }
+        None => dispatcher.dispatch().await,
+    }
 
     Ok(())
 }
@@ -0,0 +1,289 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub static METRICS: Metrics = Metrics::new();
+
+pub struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+    last_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+            last_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.last_micros.store(micros, Ordering::Relaxed);
+    }
+
+    pub fn last(&self) -> Option<Duration> {
+        (self.count.load(Ordering::Relaxed) > 0)
+            .then(|| Duration::from_micros(self.last_micros.load(Ordering::Relaxed)))
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let count = self.count.load(Ordering::Relaxed);
+
+        (count > 0).then(|| Duration::from_micros(self.sum_micros.load(Ordering::Relaxed) / count))
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let count = self.count.load(Ordering::Relaxed);
+
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{le=\"{bound}\"}} {}",
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(
+            out,
+            "{name}_sum {}",
+            self.sum_micros.load(Ordering::Relaxed) as f64 / 1e6
+        );
+        let _ = writeln!(out, "{name}_count {count}");
+    }
+}
+
+/// Process-wide counters exposed on the `/metrics` endpoint
+pub struct Metrics {
+    pub price_fetches: AtomicU64,
+    pub price_fetch_errors: AtomicU64,
+    pub price_fetch_latency: Histogram,
+    pub triggers_fired_lower: AtomicU64,
+    pub triggers_fired_higher: AtomicU64,
+    pub messages_sent: AtomicU64,
+    pub messages_failed: AtomicU64,
+    pub task_restarts: AtomicU64,
+    last_price_at: AtomicU64,
+    recent_fires: Mutex<VecDeque<SystemTime>>,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            price_fetches: AtomicU64::new(0),
+            price_fetch_errors: AtomicU64::new(0),
+            price_fetch_latency: Histogram::new(),
+            triggers_fired_lower: AtomicU64::new(0),
+            triggers_fired_higher: AtomicU64::new(0),
+            messages_sent: AtomicU64::new(0),
+            messages_failed: AtomicU64::new(0),
+            task_restarts: AtomicU64::new(0),
+            last_price_at: AtomicU64::new(0),
+            recent_fires: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_fire(&self) {
+        let now = SystemTime::now();
+
+        if let Ok(mut recent_fires) = self.recent_fires.lock() {
+            recent_fires.push_back(now);
+            prune_older_than_day(&mut recent_fires, now);
+        }
+    }
+
+    pub fn fires_last_day(&self) -> usize {
+        self.recent_fires.lock().map_or(0, |mut recent_fires| {
+            prune_older_than_day(&mut recent_fires, SystemTime::now());
+            recent_fires.len()
+        })
+    }
+
+    pub fn record_price(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs());
+
+        self.last_price_at.store(now, Ordering::Relaxed);
+    }
+
+    /// Time since the last successful price fetch, if there was one
+    pub fn price_age(&self) -> Option<Duration> {
+        let last_price_at = self.last_price_at.load(Ordering::Relaxed);
+
+        if last_price_at == 0 {
+            return None;
+        }
+
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(last_price_at))
+            .ok()
+            .or(Some(Duration::ZERO))
+    }
+
+    pub fn render(&self, triggers_lower: usize, triggers_higher: usize, chats: usize) -> String {
+        let mut out = String::new();
+
+        let mut metric = |name: &str, help: &str, kind: &str, samples: &[(&str, u64)]| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{name}{labels} {value}");
+            }
+        };
+
+        metric(
+            "price_fetches_total",
+            "Number of price fetches from the exchange",
+            "counter",
+            &[("", self.price_fetches.load(Ordering::Relaxed))],
+        );
+        metric(
+            "price_fetch_errors_total",
+            "Number of failed price fetches",
+            "counter",
+            &[("", self.price_fetch_errors.load(Ordering::Relaxed))],
+        );
+        metric(
+            "triggers",
+            "Number of active triggers",
+            "gauge",
+            &[
+                ("{type=\"lower\"}", triggers_lower as u64),
+                ("{type=\"higher\"}", triggers_higher as u64),
+            ],
+        );
+        metric(
+            "triggers_fired_total",
+            "Number of fired triggers",
+            "counter",
+            &[
+                (
+                    "{type=\"lower\"}",
+                    self.triggers_fired_lower.load(Ordering::Relaxed),
+                ),
+                (
+                    "{type=\"higher\"}",
+                    self.triggers_fired_higher.load(Ordering::Relaxed),
+                ),
+            ],
+        );
+        metric(
+            "messages_sent_total",
+            "Number of delivered notifications",
+            "counter",
+            &[("", self.messages_sent.load(Ordering::Relaxed))],
+        );
+        metric(
+            "messages_failed_total",
+            "Number of notifications which could not be delivered",
+            "counter",
+            &[("", self.messages_failed.load(Ordering::Relaxed))],
+        );
+        metric(
+            "task_restarts_total",
+            "Number of restarts of supervised background tasks",
+            "counter",
+            &[("", self.task_restarts.load(Ordering::Relaxed))],
+        );
+        metric(
+            "active_chats",
+            "Number of chats with at least one trigger",
+            "gauge",
+            &[("", chats as u64)],
+        );
+
+        self.price_fetch_latency.render(
+            &mut out,
+            "price_fetch_duration_seconds",
+            "Latency of price fetches from the exchange",
+        );
+
+        out
+    }
+}
+
+fn prune_older_than_day(times: &mut VecDeque<SystemTime>, now: SystemTime) {
+    while times
+        .front()
+        .is_some_and(|x| now.duration_since(*x).unwrap_or_default() > DAY)
+    {
+        times.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_prometheus_metrics() {
+        let metrics = Metrics::new();
+        Metrics::inc(&metrics.messages_sent);
+        Metrics::inc(&metrics.task_restarts);
+        Metrics::inc(&metrics.task_restarts);
+        metrics
+            .price_fetch_latency
+            .observe(Duration::from_millis(200));
+        metrics.price_fetch_latency.observe(Duration::from_secs(3));
+
+        let out = metrics.render(2, 1, 2);
+
+        for line in [
+            "price_fetches_total 0",
+            "triggers{type=\"lower\"} 2",
+            "triggers{type=\"higher\"} 1",
+            "messages_sent_total 1",
+            "# TYPE task_restarts_total counter",
+            "task_restarts_total 2",
+            "active_chats 2",
+            "price_fetch_duration_seconds_bucket{le=\"0.1\"} 0",
+            "price_fetch_duration_seconds_bucket{le=\"0.25\"} 1",
+            "price_fetch_duration_seconds_bucket{le=\"5\"} 2",
+            "price_fetch_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "price_fetch_duration_seconds_sum 3.2",
+            "price_fetch_duration_seconds_count 2",
+        ] {
+            assert!(out.lines().any(|x| x == line), "{line:?} is missing");
+        }
+
+        assert_eq!(
+            metrics.price_fetch_latency.mean(),
+            Some(Duration::from_millis(1600))
+        );
+        assert_eq!(
+            metrics.price_fetch_latency.last(),
+            Some(Duration::from_secs(3))
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use crate::commands::{
+    engine, export, price,
+    settings::{Language, WrongSide},
+    store::TriggerStore,
+    triggers::{self, Target},
+};
+
+use anyhow::{bail, Context, Result};
+
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::HashMap;
+use std::fs::read;
+
+use teloxide::types::ChatId;
+
+const USAGE: &str = "Usage: near-price-tracking-bot replay <prices.csv> <triggers.json>";
+
+/// Chat which owns all replayed triggers
+const CHAT_ID: ChatId = ChatId(0);
+
+/// Accepts RFC 3339 timestamps or unix seconds
+fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+
+    Utc.timestamp_opt(text.parse().ok()?, 0).single()
+}
+
+/// Reads `timestamp,price` rows, skipping a header if there is one
+fn parse_prices(content: &str) -> Result<Vec<(DateTime<Utc>, price::Price)>> {
+    let mut prices = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        // Rounded like live quotes, so thresholds compare the same way
+        let row = line.split_once(',').and_then(|(timestamp, price)| {
+            Some((
+                parse_timestamp(timestamp.trim())?,
+                price::to_tick(price::parse(price)?),
+            ))
+        });
+
+        match row {
+            Some(row) => prices.push(row),
+            None if index == 0 => continue,
+            None => bail!("Invalid price row {}: {line:?}", index + 1),
+        }
+    }
+
+    if prices.windows(2).any(|x| x[0].0 > x[1].0) {
+        bail!("Prices must be sorted by timestamp");
+    }
+
+    Ok(prices)
+}
+
+/// Adds the triggers as a chat with the `wrong_side` policy would at the first price,
+/// reporting skipped triggers on stderr. Conversions are taken as confirmed
+fn load(
+    triggers: Vec<Result<triggers::Trigger>>,
+    wrong_side: WrongSide,
+    price: price::Price,
+) -> TriggerStore<ChatId> {
+    let mut replayed = TriggerStore::default();
+
+    for (index, trigger) in triggers.into_iter().enumerate() {
+        let trigger = match trigger {
+            // Without positions to replay portfolio triggers could never fire
+            Ok(trigger) if !matches!(trigger.target, Target::Lower(_) | Target::Higher(_)) => {
+                eprintln!(
+                    "Skipping trigger #{}: portfolio triggers can't be replayed",
+                    index + 1
+                );
+                continue;
+            }
+            Ok(trigger) => trigger,
+            Err(err) => {
+                eprintln!("Skipping trigger #{}: {err}", index + 1);
+                continue;
+            }
+        };
+
+        let trigger = match triggers::apply_wrong_side(trigger, wrong_side, Some(price)) {
+            triggers::Arming::Insert(trigger) => trigger,
+            triggers::Arming::Reject(_) => {
+                eprintln!(
+                    "Skipping trigger #{}: already reached at {price}",
+                    index + 1
+                );
+                continue;
+            }
+            triggers::Arming::Convert(trigger, _) => {
+                eprintln!("Converting trigger #{} to {:?}", index + 1, trigger.target);
+                trigger
+            }
+        };
+
+        if !replayed.insert(CHAT_ID, trigger) {
+            eprintln!("Skipping duplicate trigger #{}", index + 1);
+        }
+    }
+
+    replayed
+}
+
+/// Feeds the prices through the trigger evaluator with a fake clock, returning a line per event
+fn replay(
+    mut replayed: TriggerStore<ChatId>,
+    prices: &[(DateTime<Utc>, price::Price)],
+) -> Vec<String> {
+    let portfolios = HashMap::new();
+    let (mut lines, mut fired_count) = (Vec::new(), 0);
+
+    for &(now, price) in prices {
+        let expired = engine::expire(&replayed, now);
+
+        for event in &expired {
+            lines.push(format!(
+                "{}\texpired\t{:?}",
+                now.to_rfc3339(),
+                event.target()
+            ));
+        }
+
+        engine::apply(&mut replayed, &expired);
+
+        let fired = engine::evaluate(&replayed, &portfolios, price, now);
+
+        for event in &fired {
+            match event {
+                engine::Event::Fired {
+                    target, observed, ..
+                } => {
+                    fired_count += 1;
+
+                    lines.push(format!(
+                        "{}\tfired\t{target:?}\tprice {price}\tobserved {observed:.2}",
+                        now.to_rfc3339()
+                    ));
+                }
+                engine::Event::Armed { target, .. } => {
+                    lines.push(format!(
+                        "{}\tarmed\t{target:?}\tprice {price}",
+                        now.to_rfc3339()
+                    ));
+                }
+                engine::Event::Expired { .. } => {}
+            }
+        }
+
+        engine::apply(&mut replayed, &fired);
+    }
+
+    let pending = replayed.get(&CHAT_ID).map_or(0, Vec::len);
+    lines.push(format!("{fired_count} fired, {pending} never fired"));
+
+    lines
+}
+
+/// Feeds historical prices through the trigger evaluator with a fake clock,
+/// printing when each trigger would have fired or expired instead of sending messages.
+/// Triggers are added at the first price under the wrong side policy of the exported settings
+pub fn run(args: &[String]) -> Result<()> {
+    let [prices_path, triggers_path] = args else {
+        bail!(USAGE);
+    };
+
+    let prices = read(prices_path)
+        .with_context(|| format!("Failed to read {prices_path}"))
+        .and_then(|x| String::from_utf8(x).context("Prices are not valid UTF-8"))
+        .and_then(|x| parse_prices(&x))?;
+
+    let Some(&(start, first_price)) = prices.first() else {
+        bail!("No prices to replay");
+    };
+
+    let content = read(triggers_path).with_context(|| format!("Failed to read {triggers_path}"))?;
+    let parsed = export::parse(&content, start, Language::English)?;
+    let wrong_side = parsed.wrong_side();
+
+    for line in replay(load(parsed.triggers, wrong_side, first_price), &prices) {
+        println!("{line}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rust_decimal_macros::dec;
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, 0, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_price_rows() {
+        let prices = parse_prices(
+            "timestamp,price\n\
+             2026-01-01T00:00:00Z,6.1\n\
+             \n\
+             1767225660, 6.25 \n\
+             1767225720,6.12345\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            prices,
+            [
+                (at(0), dec!(6.1)),
+                (at(1), dec!(6.25)),
+                (at(2), dec!(6.123))
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_price_rows() {
+        let err = parse_prices("2026-01-01T00:00:00Z,6.1\n2026-01-01T00:01:00Z,abc\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Invalid price row 2: \"2026-01-01T00:01:00Z,abc\""
+        );
+
+        assert!(parse_prices("2026-01-01T00:00:00Z;6.1\n2026-01-01T00:01:00Z;6.2\n").is_err());
+        assert!(parse_prices("1767225660,6.1\n1767225600,6.2\n").is_err());
+    }
+
+    #[test]
+    fn replays_fire_sequence() {
+        let content = serde_json::json!({
+            "version": 1,
+            "triggers": [
+                { "direction": "lower", "price": 6 },
+                { "direction": "higher", "price": 6.5 },
+                { "direction": "higher", "price": 6.05 },
+                { "direction": "higher", "price": 9, "expires_at": "2026-01-01T00:02:00Z" },
+                { "subject": "value", "direction": "higher", "price": 1000 },
+            ],
+            "settings": {
+                "time_zone": "UTC",
+                "quiet_hours": false,
+                "quiet_start": 23,
+                "quiet_end": 8,
+                "quiet_mode": "Silent",
+                "wrong_side": "Convert",
+            },
+        });
+        let parsed = export::parse(
+            &serde_json::to_vec(&content).unwrap(),
+            at(0),
+            Language::English,
+        )
+        .unwrap();
+        let wrong_side = parsed.wrong_side();
+
+        let prices = [
+            (at(0), dec!(6.1)),
+            (at(1), dec!(5.9)),
+            (at(2), dec!(6.6)),
+            (at(3), dec!(6.0)),
+        ];
+
+        assert_eq!(
+            replay(load(parsed.triggers, wrong_side, dec!(6.1)), &prices),
+            [
+                "2026-01-01T00:01:00+00:00\tfired\tTarget::Lower(6.00)\tprice 5.9\tobserved 5.90",
+                "2026-01-01T00:01:00+00:00\tfired\tTarget::Lower(6.05)\tprice 5.9\tobserved 5.90",
+                "2026-01-01T00:02:00+00:00\texpired\tTarget::Higher(9.00)",
+                "2026-01-01T00:02:00+00:00\tfired\tTarget::Higher(6.50)\tprice 6.6\tobserved 6.60",
+                "3 fired, 0 never fired",
+            ]
+        );
+    }
+}
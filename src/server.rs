@@ -0,0 +1,171 @@
+use crate::commands::{price, store::TriggerStore};
+use crate::metrics::METRICS;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use teloxide::prelude::*;
+
+/// Price quotes older than this make the bot not ready
+const MAX_PRICE_AGE: Duration = Duration::from_secs(60);
+const TELEGRAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct AppState {
+    bot: Bot,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    quotes: price::Quotes,
+}
+
+pub async fn serve(
+    addr: SocketAddr,
+    bot: Bot,
+    triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    quotes: price::Quotes,
+) -> Result<()> {
+    info!("Starting HTTP server on {addr}...");
+
+    let app = Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .with_state(AppState {
+            bot,
+            triggers,
+            quotes,
+        });
+
+    axum::Server::try_bind(&addr)
+        .context("Failed to bind HTTP server")?
+        .serve(app.into_make_service())
+        .await
+        .context("HTTP server failed")
+}
+
+async fn readyz(State(state): State<AppState>) -> (StatusCode, String) {
+    let mut problems = Vec::new();
+
+    // Serves the cached quote while it's fresh, so only a stale feed costs a fetch
+    match state.quotes.quote(MAX_PRICE_AGE).await {
+        Ok(quote) if quote.age() <= MAX_PRICE_AGE => {}
+        Ok(quote) => problems.push(format!("price feed is stale: {:?} old", quote.age())),
+        Err(err) => problems.push(format!("price feed is stale: {err}")),
+    }
+
+    match timeout(TELEGRAM_TIMEOUT, state.bot.get_me().into_future()).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => problems.push(format!("Telegram is unreachable: {err}")),
+        Err(_) => problems.push(String::from("Telegram is unreachable: timed out")),
+    }
+
+    if problems.is_empty() {
+        (StatusCode::OK, String::from("ready"))
+    } else {
+        warn!("Bot is not ready: {problems:?}");
+
+        (StatusCode::SERVICE_UNAVAILABLE, problems.join("\n"))
+    }
+}
+
+async fn metrics(State(state): State<AppState>) -> String {
+    let locked_triggers = state.triggers.lock().await;
+
+    let (lower, higher) =
+        locked_triggers
+            .values()
+            .flatten()
+            .fold((0, 0), |(lower, higher), trigger| {
+                if trigger.target.is_lower() {
+                    (lower + 1, higher)
+                } else {
+                    (lower, higher + 1)
+                }
+            });
+
+    METRICS.render(lower, higher, locked_triggers.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::triggers::{Target, Trigger};
+    use crate::testing::Harness;
+
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+
+    fn state(harness: &Harness) -> AppState {
+        AppState {
+            bot: harness.bot.clone(),
+            triggers: harness.triggers.clone(),
+            quotes: harness.quotes.clone(),
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_readiness() {
+        let harness = Harness::new();
+
+        let (status, body) = readyz(State(state(&harness))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, "price feed is stale: Source is unavailable");
+
+        // A stale quote stays in use while the source is down
+        let fetched_at = std::time::Instant::now()
+            .checked_sub(MAX_PRICE_AGE * 2)
+            .unwrap();
+        harness.quotes.set(dec!(6.1), fetched_at).await;
+        harness
+            .fail_next(json!({
+                "ok": false,
+                "error_code": 401,
+                "description": "Unauthorized",
+            }))
+            .await;
+
+        let (status, body) = readyz(State(state(&harness))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let problems = body.lines().collect::<Vec<_>>();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("price feed is stale: "));
+        assert!(problems[0].ends_with(" old"));
+        assert!(problems[1].starts_with("Telegram is unreachable: "));
+
+        harness.set_price(dec!(6.1)).await;
+
+        let (status, body) = readyz(State(state(&harness))).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "ready"));
+    }
+
+    #[tokio::test]
+    async fn exports_trigger_gauges() {
+        let harness = Harness::new();
+        {
+            let mut triggers = harness.triggers.lock().await;
+            triggers.insert(ChatId(1), Trigger::new(Target::Lower(dec!(6))));
+            triggers.insert(ChatId(1), Trigger::new(Target::Higher(dec!(8))));
+            triggers.insert(ChatId(2), Trigger::new(Target::Lower(dec!(5))));
+        }
+
+        let body = metrics(State(state(&harness))).await;
+
+        for line in [
+            "# TYPE triggers gauge",
+            "triggers{type=\"lower\"} 2",
+            "triggers{type=\"higher\"} 1",
+            "active_chats 2",
+            "# TYPE task_restarts_total counter",
+            "# TYPE price_fetch_duration_seconds histogram",
+        ] {
+            assert!(body.lines().any(|x| x == line), "{line:?} is missing");
+        }
+    }
+}
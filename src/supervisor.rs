@@ -0,0 +1,161 @@
+use crate::commands::notifier::Notifier;
+use crate::metrics::{Metrics, METRICS};
+
+use log::{error, info, warn};
+
+use std::collections::VecDeque;
+use std::future::Future;
+use tokio::time::{sleep, Duration, Instant};
+
+use teloxide::{types::ChatId, RequestError};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A task that runs at least this long is considered healthy again and resets the backoff
+const STABLE_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+const ALERT_WINDOW: Duration = Duration::from_secs(10 * 60);
+const ALERT_THRESHOLD: usize = 3;
+
+/// Keeps a background task running, restarting it with backoff whenever it returns or panics
+pub async fn supervise<F, Fut>(
+    name: &'static str,
+    notifier: Notifier,
+    admin_chat_id: Option<ChatId>,
+    mut task: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), RequestError>> + Send + 'static,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut restarts = 0_u64;
+    let mut recent_restarts = VecDeque::new();
+
+    loop {
+        info!("Starting {name} task...");
+
+        let started = Instant::now();
+
+        match tokio::spawn(task()).await {
+            Ok(Ok(())) => warn!("Task {name} exited unexpectedly"),
+            Ok(Err(err)) => error!("Task {name} failed: {err}"),
+            Err(err) => error!("Task {name} panicked: {err}"),
+        }
+
+        restarts += 1;
+        Metrics::inc(&METRICS.task_restarts);
+
+        let now = Instant::now();
+        if now.duration_since(started) >= STABLE_PERIOD {
+            backoff = INITIAL_BACKOFF;
+        }
+
+        recent_restarts.push_back(now);
+        while recent_restarts
+            .front()
+            .is_some_and(|x| now.duration_since(*x) > ALERT_WINDOW)
+        {
+            recent_restarts.pop_front();
+        }
+
+        warn!("Restarting {name} task in {backoff:?} (restart #{restarts})");
+
+        if recent_restarts.len() >= ALERT_THRESHOLD {
+            if let Some(chat_id) = admin_chat_id {
+                let message = format!(
+                    "Задача {name} перезапускалась {} разів за останні {} хв (усього {restarts})",
+                    recent_restarts.len(),
+                    ALERT_WINDOW.as_secs() / 60
+                );
+
+                if let Err(err) = notifier.send(chat_id, &message, None, false).await {
+                    error!("Failed to notify admin chat about {name} restarts: {err}");
+                }
+            }
+
+            recent_restarts.clear();
+        }
+
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+    use teloxide::Bot;
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_with_backoff() {
+        let start = Instant::now();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let restarts = METRICS.task_restarts.load(Ordering::Relaxed);
+
+        let supervisor = tokio::spawn(supervise(
+            "test",
+            Notifier::new(Bot::new("TEST:TOKEN")),
+            None,
+            {
+                let starts = starts.clone();
+                move || {
+                    let run = {
+                        let mut starts = starts.lock().unwrap();
+                        starts.push(start.elapsed().as_secs());
+                        starts.len()
+                    };
+
+                    async move {
+                        match run {
+                            1 => panic!("first run"),
+                            2 => Err(RequestError::RetryAfter(Duration::ZERO)),
+                            // A long run resets the backoff
+                            4 => {
+                                sleep(STABLE_PERIOD).await;
+                                Ok(())
+                            }
+                            _ => Ok(()),
+                        }
+                    }
+                }
+            },
+        ));
+
+        sleep(Duration::from_secs(311)).await;
+        supervisor.abort();
+
+        assert_eq!(*starts.lock().unwrap(), [0, 1, 3, 7, 308, 310]);
+        assert!(METRICS.task_restarts.load(Ordering::Relaxed) >= restarts + 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caps_backoff() {
+        let start = Instant::now();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+
+        let supervisor = tokio::spawn(supervise(
+            "test",
+            Notifier::new(Bot::new("TEST:TOKEN")),
+            None,
+            {
+                let starts = starts.clone();
+                move || {
+                    starts.lock().unwrap().push(start.elapsed().as_secs());
+                    async { Ok(()) }
+                }
+            },
+        ));
+
+        sleep(Duration::from_secs(300)).await;
+        supervisor.abort();
+
+        assert_eq!(
+            *starts.lock().unwrap(),
+            [0, 1, 3, 7, 15, 31, 63, 123, 183, 243]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Test harness which runs the dispatcher against a local fake Telegram Bot API server
+
+use crate::commands::{
+    history::History,
+    limits::Limits,
+    notifier::Notifier,
+    portfolio::Portfolio,
+    price::{self, Quotes, Scripted},
+    schema, settings,
+    storage::Storage,
+    store::TriggerStore,
+    Deps, State,
+};
+use crate::config::Config;
+
+use axum::{
+    extract::{Path, State as AxumState},
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Duration, Instant};
+
+use teloxide::{
+    dispatching::dialogue::InMemStorage,
+    prelude::*,
+    types::{Me, Update},
+};
+
+pub const CHAT_ID: i64 = 42;
+
+/// A request the bot made to the Bot API
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub body: Value,
+}
+
+impl Request {
+    pub fn text(&self) -> Option<&str> {
+        self.body["text"].as_str()
+    }
+
+    /// Texts of the inline keyboard buttons, row by row
+    pub fn buttons(&self) -> Vec<Vec<String>> {
+        self.body["reply_markup"]["inline_keyboard"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|row| {
+                row.as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|x| x["text"].as_str().map(String::from))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Callback data of the button with the given text
+    pub fn callback_data(&self, text: &str) -> Option<String> {
+        self.body["reply_markup"]["inline_keyboard"]
+            .as_array()?
+            .iter()
+            .filter_map(Value::as_array)
+            .flatten()
+            .find(|x| x["text"] == text)?["callback_data"]
+            .as_str()
+            .map(String::from)
+    }
+}
+
+fn user() -> Value {
+    json!({ "id": CHAT_ID, "is_bot": false, "first_name": "Test" })
+}
+
+fn me() -> Value {
+    json!({
+        "id": 1,
+        "is_bot": true,
+        "first_name": "Bot",
+        "username": "test_bot",
+        "can_join_groups": false,
+        "can_read_all_group_messages": false,
+        "supports_inline_queries": true,
+    })
+}
+
+fn message(id: i64, text: &str) -> Value {
+    json!({
+        "message_id": id,
+        "date": 0,
+        "chat": { "id": CHAT_ID, "type": "private", "first_name": "Test" },
+        "from": user(),
+        "text": text,
+    })
+}
+
+#[derive(Clone, Default)]
+struct Recorded {
+    requests: Arc<Mutex<Vec<Request>>>,
+    /// Bot API errors answered instead of the next requests
+    failures: Arc<Mutex<VecDeque<Value>>>,
+}
+
+async fn handle(
+    AxumState(recorded): AxumState<Recorded>,
+    Path((_, method)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> Json<Value> {
+    let mut requests = recorded.requests.lock().await;
+    requests.push(Request {
+        method: method.clone(),
+        body: body.clone(),
+    });
+
+    if let Some(failure) = recorded.failures.lock().await.pop_front() {
+        return Json(failure);
+    }
+
+    let result = match method.to_lowercase().as_str() {
+        "sendmessage" | "editmessagetext" | "editmessagereplymarkup" => message(
+            requests.len() as i64,
+            body["text"].as_str().unwrap_or_default(),
+        ),
+        "getme" => me(),
+        _ => json!(true),
+    };
+
+    Json(json!({ "ok": true, "result": result }))
+}
+
+/// Keeps the backup files written by handlers out of the source tree, each harness gets its own directory
+pub fn temp_storage() -> Storage {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "near-bot-tests-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    Storage::new(dir)
+}
+
+pub struct Harness {
+    pub bot: Bot,
+    pub triggers: Arc<Mutex<TriggerStore<ChatId>>>,
+    pub settings: Arc<Mutex<HashMap<ChatId, settings::Settings>>>,
+    pub history: Arc<Mutex<History>>,
+    pub portfolios: Arc<Mutex<HashMap<ChatId, Portfolio>>>,
+    pub notifier: Notifier,
+    pub limits: Limits,
+    pub quotes: Quotes,
+    pub storage: Storage,
+    pub config: Config,
+    source: Scripted,
+    dialogues: Arc<InMemStorage<State>>,
+    recorded: Recorded,
+    next_update_id: i32,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        let recorded = Recorded::default();
+        let app = Router::new()
+            .route("/:token/:method", post(handle))
+            .with_state(recorded.clone());
+
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        let bot = Bot::new("TEST:TOKEN").set_api_url(format!("http://{addr}/").parse().unwrap());
+        let source = Scripted::default();
+        let storage = temp_storage();
+
+        Self {
+            notifier: Notifier::new(bot.clone()),
+            bot,
+            triggers: Arc::default(),
+            settings: Arc::default(),
+            history: Arc::new(Mutex::new(History::new(Config::default().history))),
+            portfolios: Arc::default(),
+            limits: Limits::new(Config::default().limits, HashMap::new(), storage.clone()),
+            quotes: Quotes::new(source.clone()),
+            storage,
+            config: Config::default(),
+            source,
+            dialogues: InMemStorage::new(),
+            recorded,
+            next_update_id: 0,
+        }
+    }
+
+    /// Returns whether a handler took the update, failing the test if the handler failed
+    async fn dispatch(&mut self, update: Value) -> bool {
+        self.next_update_id += 1;
+
+        // Teloxide only recognizes the update kind if it follows `update_id`
+        let update = format!(
+            "{{\"update_id\":{},{}",
+            self.next_update_id,
+            &update.to_string()[1..]
+        );
+        let update: Update = serde_json::from_str(&update).unwrap();
+
+        let me: Me = serde_json::from_value(me()).unwrap();
+
+        let result = schema::process()
+            .dispatch(dptree::deps![
+                self.bot.clone(),
+                me,
+                update,
+                self.dialogues.clone(),
+                self.triggers.clone(),
+                self.settings.clone(),
+                self.history.clone(),
+                self.portfolios.clone(),
+                self.notifier.clone(),
+                self.limits.clone(),
+                self.quotes.clone(),
+                self.storage.clone(),
+                self.config.clone()
+            ])
+            .await;
+
+        match result {
+            std::ops::ControlFlow::Break(Ok(())) => true,
+            std::ops::ControlFlow::Break(Err(err)) => panic!("Handler failed: {err}"),
+            std::ops::ControlFlow::Continue(_) => false,
+        }
+    }
+
+    /// Dependencies of the background tasks, sharing the harness' state
+    pub fn deps(&self) -> Deps {
+        Deps::new(
+            self.triggers.clone(),
+            self.settings.clone(),
+            self.portfolios.clone(),
+            self.limits.clone(),
+            self.quotes.clone(),
+            self.storage.clone(),
+        )
+    }
+
+    /// Sets the price of this harness' quote source and refreshes the cached quote
+    pub async fn set_price(&self, price: price::Price) {
+        self.source.set(Some(price));
+        self.quotes.set(price, Instant::now().into_std()).await;
+    }
+
+    /// Sends a text message from the test user
+    pub async fn send(&mut self, text: &str) {
+        assert!(self.try_send(text).await, "Update was not handled");
+    }
+
+    /// Sends a text message from the test user, returning whether any handler took it
+    pub async fn try_send(&mut self, text: &str) -> bool {
+        self.dispatch(json!({ "message": message(0, text) })).await
+    }
+
+    /// Presses an inline keyboard button with the given callback data
+    pub async fn press(&mut self, data: &str) {
+        let handled = self
+            .dispatch(json!({
+                "callback_query": {
+                    "id": "query",
+                    "from": user(),
+                    "chat_instance": "instance",
+                    "message": message(0, ""),
+                    "data": data,
+                }
+            }))
+            .await;
+
+        assert!(handled, "Update was not handled");
+    }
+
+    /// Answers the next request with a Bot API error, e.g. `{"ok": false, "error_code": 403, ...}`
+    pub async fn fail_next(&self, failure: Value) {
+        self.recorded.failures.lock().await.push_back(failure);
+    }
+
+    /// Returns the requests made since the previous call
+    pub async fn take_requests(&self) -> Vec<Request> {
+        std::mem::take(&mut *self.recorded.requests.lock().await)
+    }
+
+    /// Returns the last message sent since the previous call, waiting for background tasks
+    pub async fn last_message(&self) -> Request {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        loop {
+            let requests = self.take_requests().await;
+
+            if let Some(request) = requests
+                .into_iter()
+                .rev()
+                .find(|x| x.method.eq_ignore_ascii_case("sendmessage"))
+            {
+                return request;
+            }
+
+            assert!(Instant::now() < deadline, "No message was sent");
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config::WebhookConfig;
+
+use anyhow::{bail, Context, Result};
+use log::{error, info};
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::TcpListener;
+
+use teloxide::{
+    prelude::*,
+    update_listeners::{webhooks, UpdateListener},
+};
+
+fn options(config: &WebhookConfig) -> Result<webhooks::Options> {
+    let mut options = webhooks::Options::new(config.addr, config.url.clone());
+
+    if let Some(secret) = &config.secret {
+        // Telegram only accepts 1-256 characters `A-Z`, `a-z`, `0-9`, `_` and `-`
+        let is_valid = (1..=256).contains(&secret.len())
+            && secret
+                .bytes()
+                .all(|x| x.is_ascii_alphanumeric() || x == b'_' || x == b'-');
+
+        if !is_valid {
+            bail!("Invalid webhook secret token");
+        }
+
+        options = options.secret_token(secret.clone());
+    }
+
+    Ok(options)
+}
+
+fn serve(
+    listener: TcpListener,
+    router: axum::Router,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<()> {
+    let server = axum::Server::from_tcp(listener).context("Failed to start webhook listener")?;
+
+    tokio::spawn(async move {
+        if let Err(err) = server
+            .serve(router.into_make_service())
+            .with_graceful_shutdown(stop)
+            .await
+        {
+            error!("Webhook listener failed: {err}");
+        }
+    });
+
+    Ok(())
+}
+
+/// Registers the webhook with Telegram and starts listening for updates on the configured address
+pub async fn listener(
+    bot: Bot,
+    config: &WebhookConfig,
+) -> Result<impl UpdateListener<Err = Infallible>> {
+    info!("Starting webhook listener on {}...", config.addr);
+
+    let tcp_listener = TcpListener::bind(config.addr).context("Failed to bind webhook listener")?;
+    tcp_listener
+        .set_nonblocking(true)
+        .context("Failed to configure webhook listener")?;
+
+    let (listener, stop, router) = webhooks::axum_to_router(bot, options(config)?)
+        .await
+        .context("Failed to set up webhook")?;
+
+    serve(tcp_listener, router, stop)?;
+
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+    use teloxide::update_listeners::AsUpdateStream;
+    use tokio::time::{timeout, Duration};
+
+    const SECRET: &str = "test-secret_1";
+
+    const UPDATE: &str = r#"{
+        "update_id": 1,
+        "message": {
+            "message_id": 1,
+            "date": 0,
+            "chat": { "id": 42, "type": "private", "first_name": "Test" },
+            "from": { "id": 42, "is_bot": false, "first_name": "Test" },
+            "text": "/getprice"
+        }
+    }"#;
+
+    async fn post(url: &str, secret: Option<&str>) -> reqwest::StatusCode {
+        let mut request = reqwest::Client::new()
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(UPDATE);
+
+        if let Some(secret) = secret {
+            request = request.header("X-Telegram-Bot-Api-Secret-Token", secret);
+        }
+
+        request.send().await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn receives_updates_with_valid_secret_only() {
+        let tcp_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        tcp_listener.set_nonblocking(true).unwrap();
+        let addr = tcp_listener.local_addr().unwrap();
+
+        let config = WebhookConfig {
+            addr,
+            url: format!("http://{addr}/").parse().unwrap(),
+            secret: Some(String::from(SECRET)),
+        };
+
+        let (mut listener, stop, router) = webhooks::axum_no_setup(options(&config).unwrap());
+        serve(tcp_listener, router, stop).unwrap();
+
+        let url = format!("http://{addr}/");
+
+        assert_eq!(post(&url, None).await, reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            post(&url, Some("wrong")).await,
+            reqwest::StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(post(&url, Some(SECRET)).await, reqwest::StatusCode::OK);
+
+        let updates = listener.as_stream();
+        tokio::pin!(updates);
+
+        let update = timeout(Duration::from_secs(5), updates.next())
+            .await
+            .expect("No update was received")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(update.id, 1);
+        assert_eq!(update.chat().map(|x| x.id), Some(ChatId(42)));
+    }
+
+    #[test]
+    fn rejects_invalid_secret() {
+        let config = WebhookConfig {
+            addr: "127.0.0.1:8443".parse().unwrap(),
+            url: "https://example.com/webhook".parse().unwrap(),
+            secret: Some(String::from("not a valid secret!")),
+        };
+
+        assert!(options(&config).is_err());
+    }
+}